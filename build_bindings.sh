@@ -0,0 +1,43 @@
+#!/usr/bin/env bash
+set -e
+
+NAME="FatCrabTrading"
+UDL="src/fatcrab_trading.udl"
+TARGETDIR="target"
+RELDIR="release"
+KOTLIN_OUTDIR="bindings/kotlin"
+PYTHON_OUTDIR="bindings/python"
+JNILIBS_OUTDIR="${KOTLIN_OUTDIR}/jniLibs"
+ANDROID_ABIS=("arm64-v8a" "armeabi-v7a" "x86_64")
+
+# Host cdylib, loaded by the Python bindings and by Kotlin on the JVM
+case "$(uname -s)" in
+  Darwin) CDYLIB_NAME="libfatcrab_trading.dylib" ;;
+  MINGW* | MSYS* | CYGWIN*) CDYLIB_NAME="fatcrab_trading.dll" ;;
+  *) CDYLIB_NAME="libfatcrab_trading.so" ;;
+esac
+
+cargo build --release
+
+# Android libraries, one per ABI under jniLibs (aarch64, armv7 and x86_64-linux-android).
+# Needs cargo-ndk and the Android NDK. Set SKIP_ANDROID=1 to build for the host only.
+if [ "${SKIP_ANDROID}" != "1" ]; then
+  if ! cargo ndk --version > /dev/null 2>&1; then
+    echo "cargo-ndk not found, install it with 'cargo install cargo-ndk' or set SKIP_ANDROID=1" >&2
+    exit 1
+  fi
+  ABI_ARGS=()
+  for ABI in "${ANDROID_ABIS[@]}"; do
+    ABI_ARGS+=("-t" "${ABI}")
+  done
+  cargo ndk "${ABI_ARGS[@]}" -o "${JNILIBS_OUTDIR}" build --release
+fi
+
+# UniFfi bindgen
+cargo run --bin uniffi-bindgen generate "${UDL}" --language kotlin --out-dir "${KOTLIN_OUTDIR}"
+cargo run --bin uniffi-bindgen generate "${UDL}" --language python --out-dir "${PYTHON_OUTDIR}"
+
+cp "${TARGETDIR}/${RELDIR}/${CDYLIB_NAME}" "${PYTHON_OUTDIR}/"
+cp "${TARGETDIR}/${RELDIR}/${CDYLIB_NAME}" "${KOTLIN_OUTDIR}/"
+
+echo "${NAME} Kotlin bindings in ${KOTLIN_OUTDIR}, Python bindings in ${PYTHON_OUTDIR}"
@@ -0,0 +1,153 @@
+#!/usr/bin/env python3
+# End to end smoke test of the Python bindings against a local regtest setup.
+#
+# Needs the bindings from build_bindings.sh, an Electrum server on regtest and a Nostr relay:
+#   ELECTRUM_URL  defaults to tcp://localhost:50000
+#   RELAY_URL     defaults to ws://localhost:8080
+#
+# Run with: python3 scripts/smoke_test.py
+
+import os
+import sys
+import tempfile
+import time
+import uuid
+
+sys.path.insert(0, os.path.join(os.path.dirname(__file__), "..", "bindings", "python"))
+
+from FatCrabTrading import (  # noqa: E402
+    BlockchainInfo,
+    FatCrabLogSink,
+    FatCrabOrder,
+    FatCrabOrderType,
+    FilterLevel,
+    FatCrabTrader,
+    Network,
+    ProductionLevel,
+    RelayAddr,
+    RelayStatus,
+    init_tracing,
+)
+
+ELECTRUM_URL = os.environ.get("ELECTRUM_URL", "tcp://localhost:50000")
+RELAY_URL = os.environ.get("RELAY_URL", "ws://localhost:8080")
+RELAY_CONNECT_TIMEOUT = 10
+ORDER_QUERY_TIMEOUT = 10
+RELAY_REMOVE_TIMEOUT = 10
+
+
+def check(condition, message):
+    if not condition:
+        print(f"FAILED - {message}")
+        sys.exit(1)
+    print(f"ok - {message}")
+
+
+def wait_for(timeout, poll):
+    deadline = time.time() + timeout
+    while time.time() < deadline:
+        result = poll()
+        if result:
+            return result
+        time.sleep(0.5)
+    return None
+
+
+def connect_relay(trader, name):
+    trader.add_relays([RelayAddr(url=RELAY_URL, socket_addr=None)])
+    connected = wait_for(
+        RELAY_CONNECT_TIMEOUT,
+        lambda: any(relay.status == RelayStatus.CONNECTED for relay in trader.get_relays()),
+    )
+    check(connected, f"{name} connected to relay {RELAY_URL}")
+    return trader.get_relays()[0].url
+
+
+def find_order(trader, trade_uuid):
+    try:
+        envelopes = trader.query_orders(FatCrabOrderType.SELL)
+    except Exception:
+        return None
+    for envelope in envelopes:
+        if envelope.order().trade_uuid == trade_uuid:
+            return envelope
+    return None
+
+
+def relay_terminated(trader, relay_url):
+    for event in trader.next_events(16, 500):
+        if not event.is_relay_status():
+            continue
+        notif = event.relay_status_notif
+        if notif.url == relay_url and notif.status == RelayStatus.TERMINATED:
+            return True
+    return False
+
+
+def main():
+    init_tracing(FilterLevel.INFO, True, True, [FatCrabLogSink.STDERR()])
+
+    with tempfile.TemporaryDirectory() as maker_dir, tempfile.TemporaryDirectory() as taker_dir:
+        info = BlockchainInfo.ELECTRUM(url=ELECTRUM_URL, network=Network.REGTEST)
+        maker = FatCrabTrader(ProductionLevel.DEBUG, info, maker_dir)
+        taker = FatCrabTrader(ProductionLevel.DEBUG, info, taker_dir)
+
+        check(maker.get_network() == Network.REGTEST, "trader runs on regtest")
+        mnemonic = maker.wallet_bip39_mnemonic()
+        check(len(mnemonic.split()) in (12, 24), "wallet has a mnemonic")
+        check(len(maker.nostr_pubkey()) == 64, "trader has a Nostr pubkey")
+        check(maker.nostr_pubkey() != taker.nostr_pubkey(), "traders have their own Nostr keys")
+
+        address = maker.wallet_generate_receive_address()
+        check(address.startswith("bcrt1"), f"receive address {address}")
+
+        maker.wallet_blockchain_sync()
+        height = maker.wallet_blockchain_height()
+        check(height > 0, f"wallet synced to height {height}")
+        balances = maker.wallet_balances()
+        print(f"ok - confirmed balance {balances.confirmed}")
+        maker.wallet_blockchain_sync()
+        check(maker.wallet_blockchain_height() >= height, "wallet height does not go back")
+
+        # An order posted by the maker reaches the taker over the relay
+        maker_relay_url = connect_relay(maker, "maker")
+        taker_relay_url = connect_relay(taker, "taker")
+        trade_uuid = str(uuid.uuid4())
+        order = FatCrabOrder(
+            order_type=FatCrabOrderType.SELL,
+            trade_uuid=trade_uuid,
+            amount=100.0,
+            price=0.0001,
+            network=Network.REGTEST,
+        )
+        sell_maker = maker.new_sell_maker(order)
+        sell_maker.post_new_order()
+        envelope = wait_for(ORDER_QUERY_TIMEOUT, lambda: find_order(taker, trade_uuid))
+        check(envelope is not None, f"taker found order {trade_uuid}")
+        check(envelope.pubkey() == maker.nostr_pubkey(), "order came from the maker")
+
+        # Once removed, the relay is reported gone and nothing is read from it anymore.
+        # Events are only queued once they are first asked for.
+        taker.next_events(64, 0)
+        taker.remove_relay(taker_relay_url)
+        check(
+            all(relay.url != taker_relay_url for relay in taker.get_relays()),
+            f"relay {taker_relay_url} removed",
+        )
+        check(
+            wait_for(RELAY_REMOVE_TIMEOUT, lambda: relay_terminated(taker, taker_relay_url)),
+            f"relay {taker_relay_url} reported terminated",
+        )
+        check(find_order(taker, trade_uuid) is None, "removed relay is no longer queried")
+        check(
+            any(relay.url == maker_relay_url for relay in maker.get_relays()),
+            "maker keeps its own relay",
+        )
+
+        maker.shutdown()
+        taker.shutdown()
+        print("Smoke test passed")
+
+
+if __name__ == "__main__":
+    main()
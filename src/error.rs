@@ -15,8 +15,10 @@ pub enum FatCrabError {
     JoinError { description: String },
     SerdesJson { description: String },
     UrlParse { description: String },
+    Nostr { description: String },
     MpscSend { description: String },
     OneshotRecv { description: String },
+    InvalidStateTransition { from: String, action: String },
 }
 
 impl Error for FatCrabError {}
@@ -76,12 +78,21 @@ impl Display for FatCrabError {
             FatCrabError::UrlParse { description } => {
                 format!("FatCrab-Error | UrlParse - {}", description)
             }
+            FatCrabError::Nostr { description } => {
+                format!("FatCrab-Error | Nostr - {}", description)
+            }
             FatCrabError::MpscSend { description } => {
                 format!("FatCrab-Error | MpscSend - {}", description)
             }
             FatCrabError::OneshotRecv { description } => {
                 format!("FatCrab-Error | OneshotRecv - {}", description)
             }
+            FatCrabError::InvalidStateTransition { from, action } => {
+                format!(
+                    "FatCrab-Error | InvalidStateTransition - {} not allowed from {}",
+                    action, from
+                )
+            }
         };
         write!(f, "{}", error_string)
     }
@@ -102,3 +113,27 @@ impl From<url::ParseError> for FatCrabError {
         }
     }
 }
+
+impl From<nostr_sdk::client::Error> for FatCrabError {
+    fn from(e: nostr_sdk::client::Error) -> FatCrabError {
+        FatCrabError::Nostr {
+            description: e.to_string(),
+        }
+    }
+}
+
+impl From<std::io::Error> for FatCrabError {
+    fn from(e: std::io::Error) -> FatCrabError {
+        FatCrabError::Io {
+            description: e.to_string(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for FatCrabError {
+    fn from(e: serde_json::Error) -> FatCrabError {
+        FatCrabError::SerdesJson {
+            description: e.to_string(),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::maker::{FatCrabMakerNotifOfferStruct, FatCrabMakerNotifPeerStruct};
+use crate::notif::TradeNotif;
+use crate::order::FatCrabOrderType;
+use crate::relay::FatCrabRelayStatusNotifStruct;
+use crate::taker::{FatCrabTakerNotifPeerStruct, FatCrabTakerNotifTradeRspStruct};
+use crate::trade::FatCrabTradeRole;
+use crate::trader::Balances;
+
+const EVENT_QUEUE_CAPACITY: usize = 1024;
+
+#[derive(Clone)]
+pub enum FatCrabEvent {
+    TradeRestored {
+        trade_uuid: String,
+        role: FatCrabTradeRole,
+        order_type: FatCrabOrderType,
+    },
+    MakerOffer {
+        trade_uuid: String,
+        offer_notif: FatCrabMakerNotifOfferStruct,
+    },
+    MakerPeer {
+        trade_uuid: String,
+        peer_notif: FatCrabMakerNotifPeerStruct,
+    },
+    TakerTradeRsp {
+        trade_uuid: String,
+        trade_rsp_notif: FatCrabTakerNotifTradeRspStruct,
+    },
+    TakerPeer {
+        trade_uuid: String,
+        peer_notif: FatCrabTakerNotifPeerStruct,
+    },
+    RelayStatus {
+        relay_status_notif: FatCrabRelayStatusNotifStruct,
+    },
+    WalletSynced {
+        height: u32,
+        balances: Balances,
+    },
+    WalletTxSent {
+        txid: String,
+        address: String,
+        amount: u64,
+    },
+}
+
+impl FatCrabEvent {
+    pub(crate) fn from_notif(trade_uuid: &str, notif: TradeNotif) -> Self {
+        let trade_uuid = trade_uuid.to_string();
+        match notif {
+            TradeNotif::MakerOffer(offer_notif) => FatCrabEvent::MakerOffer {
+                trade_uuid,
+                offer_notif,
+            },
+            TradeNotif::MakerPeer(peer_notif) => FatCrabEvent::MakerPeer {
+                trade_uuid,
+                peer_notif,
+            },
+            TradeNotif::TakerTradeRsp(trade_rsp_notif) => FatCrabEvent::TakerTradeRsp {
+                trade_uuid,
+                trade_rsp_notif,
+            },
+            TradeNotif::TakerPeer(peer_notif) => FatCrabEvent::TakerPeer {
+                trade_uuid,
+                peer_notif,
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+struct EventQueueState {
+    enabled: bool,
+    events: VecDeque<FatCrabEvent>,
+}
+
+// Events are only queued once the app starts polling, so an app relying solely
+// on delegates never accumulates events nobody will drain
+pub(crate) struct EventQueue {
+    state: Mutex<EventQueueState>,
+    ready: Condvar,
+}
+
+impl EventQueue {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(EventQueueState::default()),
+            ready: Condvar::new(),
+        }
+    }
+
+    // Returns true only for the call that actually enabled the queue
+    pub(crate) fn enable(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        !std::mem::replace(&mut state.enabled, true)
+    }
+
+    pub(crate) fn push(&self, event: FatCrabEvent) {
+        let mut state = self.state.lock().unwrap();
+        if !state.enabled {
+            return;
+        }
+        if state.events.len() >= EVENT_QUEUE_CAPACITY {
+            warn!("Event queue full - dropping oldest event");
+            state.events.pop_front();
+        }
+        state.events.push_back(event);
+        self.ready.notify_all();
+    }
+
+    // Waits up to the timeout for at least one event, then returns up to max events in order
+    pub(crate) fn next(&self, max: usize, timeout: Duration) -> Vec<FatCrabEvent> {
+        if max == 0 {
+            return Vec::new();
+        }
+        let state = self.state.lock().unwrap();
+        let (mut state, _) = self
+            .ready
+            .wait_timeout_while(state, timeout, |state| state.events.is_empty())
+            .unwrap();
+        let count = max.min(state.events.len());
+        state.events.drain(..count).collect()
+    }
+}
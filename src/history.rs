@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::error::FatCrabError;
+use crate::order::FatCrabOrderType;
+use crate::persist::{ffi_data_dir, load_json, now_timestamp, save_json};
+use crate::trade::{FatCrabTrade, FatCrabTradeRole, FatCrabTradeState};
+use crate::types::Network;
+
+const TRADE_HISTORY_FILENAME: &str = "trade_history.json";
+
+const CSV_HEADER: &str = "trade_uuid,role,order_type,amount,price,network,final_state,counterparty_pubkey,peer_btc_txid,btc_confirmations,fatcrab_txid,started_at,ended_at";
+
+pub enum FatCrabHistoryFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FatCrabTradeRecord {
+    pub trade_uuid: String,
+    pub role: FatCrabTradeRole,
+    #[serde(with = "order_type_serde")]
+    pub order_type: FatCrabOrderType,
+    pub amount: f64, // in FC
+    pub price: f64,
+    pub network: Network,
+    pub final_state: FatCrabTradeState,
+    pub counterparty_pubkey: Option<String>,
+    // Only known to the side receiving BTC, the Trading Engine does not hand back
+    // the txid of the BTC it sends on a trade's behalf
+    #[serde(alias = "btc_txid")]
+    pub peer_btc_txid: Option<String>,
+    // Confirmations of peer_btc_txid as the trade ended
+    pub btc_confirmations: Option<u32>,
+    pub fatcrab_txid: Option<String>,
+    pub started_at: u64,
+    pub ended_at: u64,
+}
+
+// What the FFI sees of a trade in flight that the Trading Engine does not keep around
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct TradeObservations {
+    #[serde(alias = "btc_txid")]
+    peer_btc_txid: Option<String>,
+    btc_confirmations: Option<u32>,
+    fatcrab_txid: Option<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct TradeHistoryData {
+    records: Vec<FatCrabTradeRecord>,
+    observations: HashMap<String, TradeObservations>,
+}
+
+pub(crate) struct TradeHistory {
+    path: PathBuf,
+    data: Mutex<TradeHistoryData>,
+}
+
+impl TradeHistory {
+    pub(crate) fn new(app_dir_path: &str) -> Self {
+        let path = ffi_data_dir(app_dir_path).join(TRADE_HISTORY_FILENAME);
+        let data = load_json(&path);
+        Self {
+            path,
+            data: Mutex::new(data),
+        }
+    }
+
+    pub(crate) fn observe_peer_btc_txid(&self, trade_uuid: &str, btc_txid: String) {
+        self.observe(trade_uuid, |observations| {
+            observations.peer_btc_txid = Some(btc_txid)
+        });
+    }
+
+    pub(crate) fn observe_btc_confirmations(&self, trade_uuid: &str, confirmations: u32) {
+        self.observe(trade_uuid, |observations| {
+            observations.btc_confirmations = Some(confirmations)
+        });
+    }
+
+    pub(crate) fn observe_fatcrab_txid(&self, trade_uuid: &str, fatcrab_txid: String) {
+        self.observe(trade_uuid, |observations| {
+            observations.fatcrab_txid = Some(fatcrab_txid)
+        });
+    }
+
+    fn observe(&self, trade_uuid: &str, update: impl FnOnce(&mut TradeObservations)) {
+        let mut data = self.data.lock().unwrap();
+        if data
+            .records
+            .iter()
+            .any(|record| record.trade_uuid == trade_uuid)
+        {
+            return;
+        }
+        update(data.observations.entry(trade_uuid.to_string()).or_default());
+        self.save(&data);
+    }
+
+    // Only the first record of a trade is kept, later calls for the same trade are ignored
+    pub(crate) fn record(&self, trade: &FatCrabTrade) {
+        let mut data = self.data.lock().unwrap();
+        if data
+            .records
+            .iter()
+            .any(|record| record.trade_uuid == trade.trade_uuid)
+        {
+            return;
+        }
+        let observations = data
+            .observations
+            .remove(&trade.trade_uuid)
+            .unwrap_or_default();
+
+        data.records.push(FatCrabTradeRecord {
+            trade_uuid: trade.trade_uuid.clone(),
+            role: trade.role,
+            order_type: trade.order_type.clone(),
+            amount: trade.order.amount,
+            price: trade.order.price,
+            network: trade.order.network.clone(),
+            final_state: trade.state,
+            counterparty_pubkey: trade.peer_pubkey.clone(),
+            peer_btc_txid: observations.peer_btc_txid,
+            btc_confirmations: observations.btc_confirmations,
+            fatcrab_txid: observations.fatcrab_txid,
+            started_at: trade.created_at,
+            ended_at: now_timestamp(),
+        });
+        self.save(&data);
+    }
+
+    pub(crate) fn has_record(&self, trade_uuid: &str) -> bool {
+        self.data
+            .lock()
+            .unwrap()
+            .records
+            .iter()
+            .any(|record| record.trade_uuid == trade_uuid)
+    }
+
+    pub(crate) fn records(&self) -> Vec<FatCrabTradeRecord> {
+        self.data.lock().unwrap().records.clone()
+    }
+
+    pub(crate) fn export(&self, format: FatCrabHistoryFormat) -> Result<String, FatCrabError> {
+        let records = self.records();
+        match format {
+            FatCrabHistoryFormat::Json => Ok(serde_json::to_string_pretty(&records)?),
+            FatCrabHistoryFormat::Csv => {
+                let mut csv = String::from(CSV_HEADER);
+                csv.push('\n');
+                for record in records.iter() {
+                    let fields = [
+                        record.trade_uuid.clone(),
+                        enum_name(&record.role),
+                        order_type_name(&record.order_type).to_string(),
+                        record.amount.to_string(),
+                        record.price.to_string(),
+                        enum_name(&record.network),
+                        enum_name(&record.final_state),
+                        record.counterparty_pubkey.clone().unwrap_or_default(),
+                        record.peer_btc_txid.clone().unwrap_or_default(),
+                        record
+                            .btc_confirmations
+                            .map(|confirmations| confirmations.to_string())
+                            .unwrap_or_default(),
+                        record.fatcrab_txid.clone().unwrap_or_default(),
+                        record.started_at.to_string(),
+                        record.ended_at.to_string(),
+                    ];
+                    let row: Vec<String> = fields.iter().map(|field| csv_escape(field)).collect();
+                    csv.push_str(&row.join(","));
+                    csv.push('\n');
+                }
+                Ok(csv)
+            }
+        }
+    }
+
+    fn save(&self, data: &TradeHistoryData) {
+        if let Err(error) = save_json(&self.path, data) {
+            warn!("Failed to persist trade history - {}", error);
+        }
+    }
+}
+
+fn order_type_name(order_type: &FatCrabOrderType) -> &'static str {
+    match order_type {
+        FatCrabOrderType::Buy => "Buy",
+        FatCrabOrderType::Sell => "Sell",
+    }
+}
+
+fn enum_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains(|c: char| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+mod order_type_serde {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use super::order_type_name;
+    use crate::order::FatCrabOrderType;
+
+    pub fn serialize<S: Serializer>(
+        order_type: &FatCrabOrderType,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(order_type_name(order_type))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<FatCrabOrderType, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "Buy" => Ok(FatCrabOrderType::Buy),
+            "Sell" => Ok(FatCrabOrderType::Sell),
+            other => Err(D::Error::custom(format!("unknown order type {}", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_escape_leaves_plain_fields() {
+        assert_eq!(csv_escape("abc-123"), "abc-123");
+        assert_eq!(csv_escape(""), "");
+    }
+
+    #[test]
+    fn csv_escape_quotes_separators_and_line_breaks() {
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("a\nb"), "\"a\nb\"");
+        assert_eq!(csv_escape("a\rb"), "\"a\rb\"");
+    }
+
+    #[test]
+    fn csv_escape_doubles_quotes() {
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::error::FatCrabError;
+use crate::persist::{ffi_data_dir, now_timestamp};
+
+const TRADE_JOURNAL_DIR: &str = "journal";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FatCrabJournalEntry {
+    pub timestamp: u64,
+    pub trigger: String,
+    pub from_state: Option<String>,
+    pub to_state: Option<String>,
+    pub error: Option<String>,
+}
+
+impl FatCrabJournalEntry {
+    pub(crate) fn new(
+        trigger: impl ToString,
+        from_state: Option<&str>,
+        to_state: Option<&str>,
+        error: Option<&FatCrabError>,
+    ) -> Self {
+        Self {
+            timestamp: now_timestamp(),
+            trigger: trigger.to_string(),
+            from_state: from_state.map(|state| state.to_string()),
+            to_state: to_state.map(|state| state.to_string()),
+            error: error.map(|error| error.to_string()),
+        }
+    }
+}
+
+// Append-only, one JSON line per entry, one file per trade
+pub(crate) struct TradeJournal {
+    dir: PathBuf,
+    // Last state journaled per trade, read from the file once. Also serializes appends.
+    last_states: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl TradeJournal {
+    pub(crate) fn new(app_dir_path: &str) -> Self {
+        Self {
+            dir: ffi_data_dir(app_dir_path).join(TRADE_JOURNAL_DIR),
+            last_states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn path(&self, trade_uuid: &str) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", trade_uuid))
+    }
+
+    pub(crate) fn append(&self, trade_uuid: &str, entry: FatCrabJournalEntry) {
+        let mut last_states = self.last_states.lock().unwrap();
+        if let Err(error) = self.write_entry(trade_uuid, &entry) {
+            warn!(
+                "Failed to append journal entry for trade {} - {}",
+                trade_uuid, error
+            );
+        }
+        if let Some(to_state) = entry.to_state {
+            last_states.insert(trade_uuid.to_string(), Some(to_state));
+        }
+    }
+
+    fn write_entry(
+        &self,
+        trade_uuid: &str,
+        entry: &FatCrabJournalEntry,
+    ) -> Result<(), FatCrabError> {
+        fs::create_dir_all(&self.dir)?;
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(trade_uuid))?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    pub(crate) fn entries(
+        &self,
+        trade_uuid: &str,
+    ) -> Result<Vec<FatCrabJournalEntry>, FatCrabError> {
+        let data = match fs::read_to_string(self.path(trade_uuid)) {
+            Ok(data) => data,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+
+        // A crash mid-append can only ever damage the last line, skip anything unreadable
+        Ok(data
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    // The state a trade was last journaled in, for transitions driven by the peer
+    pub(crate) fn last_state(&self, trade_uuid: &str) -> Option<String> {
+        let mut last_states = self.last_states.lock().unwrap();
+        if let Some(last_state) = last_states.get(trade_uuid) {
+            return last_state.clone();
+        }
+        let last_state = self
+            .entries(trade_uuid)
+            .ok()?
+            .into_iter()
+            .rev()
+            .find_map(|entry| entry.to_state);
+        last_states.insert(trade_uuid.to_string(), last_state.clone());
+        last_state
+    }
+}
@@ -1,49 +1,58 @@
 mod error;
+mod event;
+mod history;
+mod journal;
+mod logging;
 mod maker;
+mod nip11;
+mod nip65;
+mod notif;
 mod offer;
 mod order;
 mod peer;
+mod persist;
+mod relay;
 mod taker;
+mod trade;
 mod trade_rsp;
 mod trader;
 mod types;
 
 use once_cell::sync::Lazy;
 use tokio::runtime::Runtime;
-use tracing_oslog::OsLogger;
-use tracing_subscriber::filter::LevelFilter;
-use tracing_subscriber::{layer::SubscriberExt, Layer};
 
 uniffi::include_scaffolding!("fatcrab_trading");
 static RUNTIME: Lazy<Runtime> = Lazy::new(|| Runtime::new().expect("Can't start Tokio runtime"));
 
 use error::FatCrabError;
+use event::FatCrabEvent;
+use history::{FatCrabHistoryFormat, FatCrabTradeRecord};
+use journal::FatCrabJournalEntry;
+use logging::{
+    init_tracing, init_tracing_for_oslog, register_log_delegate, set_log_directives, set_log_level,
+    unregister_log_delegate, FatCrabLogRecord, FatCrabLogSink,
+};
 use maker::{
-    FatCrabBuyMaker, FatCrabMakerNotifOfferStruct, FatCrabMakerNotifPeerStruct, FatCrabMakerState,
-    FatCrabSellMaker,
+    FatCrabBuyMaker, FatCrabMakerAction, FatCrabMakerNotifOfferStruct, FatCrabMakerNotifPeerStruct,
+    FatCrabMakerState, FatCrabSellMaker,
 };
+use nip11::{FatCrabRelayPolicyAction, FatCrabRelayProbe, FatCrabRelayRequirements};
+use nip65::FatCrabPeerRelay;
+use notif::{FatCrabNotifBufferConfig, FatCrabNotifOverflowPolicy, FatCrabNotifOverflowStruct};
 use offer::FatCrabOfferEnvelope;
 use order::{FatCrabOrder, FatCrabOrderEnvelope, FatCrabOrderType};
 use peer::{FatCrabPeerEnvelope, FatCrabPeerMessage};
+use relay::{FatCrabRelayReconnectPolicy, FatCrabRelayStatusNotifStruct};
 use taker::{
-    FatCrabBuyTaker, FatCrabSellTaker, FatCrabTakerNotifPeerStruct,
+    FatCrabBuyTaker, FatCrabSellTaker, FatCrabTakerAction, FatCrabTakerNotifPeerStruct,
     FatCrabTakerNotifTradeRspStruct, FatCrabTakerState,
 };
+use trade::{FatCrabTrade, FatCrabTradeFilter, FatCrabTradeRole, FatCrabTradeState};
 use trade_rsp::{FatCrabTradeRsp, FatCrabTradeRspEnvelope, FatCrabTradeRspType};
-use trader::{Balances, FatCrabTrader};
+use trader::{Balances, FatCrabTrader, FatCrabTraderOptions};
 use types::{
-    Auth, BlockchainInfo, FatCrabMakerNotifDelegate, FatCrabTakerNotifDelegate, FilterLevel,
-    Network, ProductionLevel, RelayAddr, RelayInfo, RelayInformationDocument, RelayStatus,
+    Auth, BlockchainInfo, FatCrabLogDelegate, FatCrabMakerNotifDelegate,
+    FatCrabNotifOverflowDelegate, FatCrabRelayNotifDelegate, FatCrabTakerNotifDelegate,
+    FatCrabTraderNotifDelegate, FilterLevel, Network, ProductionLevel, RelayAddr, RelayHealth,
+    RelayInfo, RelayInformationDocument, RelayStatus,
 };
-
-// Init tracing for Apple unified logging system
-pub fn init_tracing_for_oslog(level: FilterLevel, log_timestamp: bool, log_level: bool) {
-    let level_filter: LevelFilter = level.into();
-    let collector = tracing_subscriber::registry().with(
-        OsLogger::new("dev.n3xb.io.fatcrab-trading", "default")
-            .log_timestamp(log_timestamp)
-            .log_level(log_level)
-            .with_filter(level_filter),
-    );
-    tracing::subscriber::set_global_default(collector).expect("failed to set global subscriber");
-}
@@ -0,0 +1,361 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+
+use once_cell::sync::Lazy;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{info_span, Event, Span, Subscriber};
+#[cfg(feature = "file-log")]
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::{Directive, EnvFilter, LevelFilter};
+use tracing_subscriber::layer::{Context, Layered};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{layer::SubscriberExt, reload, Layer, Registry};
+
+use crate::error::FatCrabError;
+use crate::persist::now_timestamp;
+use crate::types::{FatCrabLogDelegate, FilterLevel};
+
+type FilterHandle = reload::Handle<EnvFilter, Registry>;
+type FilteredRegistry = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+type BoxedLayer = Box<dyn Layer<FilteredRegistry> + Send + Sync>;
+
+// The level and per-module directives every sink is filtered by, changeable at any time
+struct LogFilter {
+    handle: FilterHandle,
+    // As given to the call that set tracing up
+    sinks: Vec<FatCrabLogSink>,
+    level: LevelFilter,
+    directives: Vec<Directive>,
+}
+
+impl LogFilter {
+    fn build(&self) -> EnvFilter {
+        let mut env_filter = EnvFilter::default().add_directive(self.level.into());
+        for directive in self.directives.iter() {
+            env_filter = env_filter.add_directive(directive.clone());
+        }
+        env_filter
+    }
+
+    fn reload(&self) -> Result<(), FatCrabError> {
+        self.handle
+            .reload(self.build())
+            .map_err(|e| FatCrabError::Simple {
+                description: format!("Cannot change log filter - {}", e),
+            })
+    }
+}
+
+static LOG_FILTER: Lazy<Mutex<Option<LogFilter>>> = Lazy::new(|| Mutex::new(None));
+
+#[cfg(all(feature = "oslog", target_vendor = "apple"))]
+const OSLOG_SUBSYSTEM: &str = "dev.n3xb.io.fatcrab-trading";
+#[cfg(feature = "file-log")]
+const LOG_FILE_PREFIX: &str = "fatcrab-trading.log";
+
+#[derive(Clone, PartialEq)]
+pub enum FatCrabLogSink {
+    Stderr,
+    // Rotated daily, keeping at most max_files files in dir_path. Needs the file-log feature.
+    File { dir_path: String, max_files: u32 },
+    // Apple unified logging system, ignored on other platforms and without the oslog feature
+    OsLog,
+}
+
+pub struct FatCrabLogRecord {
+    pub level: FilterLevel,
+    pub target: String,
+    pub message: String,
+    pub fields: HashMap<String, String>,
+    // Names of the spans the event happened in, outermost first
+    pub spans: Vec<String>,
+    // Fields of those spans, inner spans taking precedence
+    pub span_fields: HashMap<String, String>,
+    // Picked from the event or its spans when one of them carries a trade_uuid field
+    pub trade_uuid: Option<String>,
+    pub timestamp: u64,
+}
+
+static LOG_DELEGATE: Lazy<RwLock<Option<Arc<dyn FatCrabLogDelegate>>>> =
+    Lazy::new(|| RwLock::new(None));
+
+thread_local! {
+    // Set while the delegate is called, so whatever it logs is not handed back to it
+    static IN_LOG_DELEGATE: Cell<bool> = const { Cell::new(false) };
+}
+
+// Works with whichever sinks init_tracing was given, as the delegate layer is always installed
+pub fn register_log_delegate(delegate: Arc<dyn FatCrabLogDelegate>) {
+    *LOG_DELEGATE.write().unwrap() = Some(delegate);
+}
+
+pub fn unregister_log_delegate() {
+    *LOG_DELEGATE.write().unwrap() = None;
+}
+
+// Entered around whatever the FFI does for a trade, so the records it logs carry its trade_uuid
+pub(crate) fn trade_span(trade_uuid: &str) -> Span {
+    info_span!("trade", trade_uuid = %trade_uuid)
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: HashMap<String, String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+}
+
+// Kept in each span's extensions so events can carry the context they happened in
+struct SpanFields(HashMap<String, String>);
+
+// Calls the delegate on the thread that logged, without holding any lock. Events logged
+// by the delegate itself are dropped rather than delivered to it again.
+struct DelegateLayer;
+
+impl<S> Layer<S> for DelegateLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(SpanFields(visitor.fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        values.record(&mut visitor);
+        if let Some(span_fields) = span.extensions_mut().get_mut::<SpanFields>() {
+            span_fields.0.extend(visitor.fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if IN_LOG_DELEGATE.with(|in_delegate| in_delegate.get()) {
+            return;
+        }
+        let Some(delegate) = LOG_DELEGATE.read().unwrap().clone() else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+
+        let mut spans = Vec::new();
+        let mut span_fields = HashMap::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                spans.push(span.name().to_string());
+                if let Some(fields) = span.extensions().get::<SpanFields>() {
+                    span_fields.extend(fields.0.clone());
+                }
+            }
+        }
+        let trade_uuid = visitor
+            .fields
+            .get("trade_uuid")
+            .or_else(|| span_fields.get("trade_uuid"))
+            .cloned();
+
+        let record = FatCrabLogRecord {
+            level: event.metadata().level().into(),
+            target: event.metadata().target().to_string(),
+            message: visitor.message,
+            fields: visitor.fields,
+            spans,
+            span_fields,
+            trade_uuid,
+            timestamp: now_timestamp(),
+        };
+        IN_LOG_DELEGATE.with(|in_delegate| in_delegate.set(true));
+        delegate.on_log(record);
+        IN_LOG_DELEGATE.with(|in_delegate| in_delegate.set(false));
+    }
+}
+
+fn sink_layer(
+    sink: FatCrabLogSink,
+    log_timestamp: bool,
+    log_level: bool,
+) -> Result<Option<BoxedLayer>, String> {
+    let layer: BoxedLayer = match sink {
+        FatCrabLogSink::Stderr => {
+            let layer = tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_level(log_level);
+            if log_timestamp {
+                Box::new(layer)
+            } else {
+                Box::new(layer.without_time())
+            }
+        }
+        #[cfg(feature = "file-log")]
+        FatCrabLogSink::File {
+            dir_path,
+            max_files,
+        } => {
+            let appender = RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(LOG_FILE_PREFIX)
+                .max_log_files(max_files.max(1) as usize)
+                .build(&dir_path)
+                .map_err(|e| format!("Cannot log to {} - {}", dir_path, e))?;
+            let layer = tracing_subscriber::fmt::layer()
+                .with_writer(appender)
+                .with_ansi(false)
+                .with_level(log_level);
+            if log_timestamp {
+                Box::new(layer)
+            } else {
+                Box::new(layer.without_time())
+            }
+        }
+        #[cfg(not(feature = "file-log"))]
+        FatCrabLogSink::File { .. } => {
+            return Err("File sink is not available without the file-log feature".to_string())
+        }
+        #[cfg(all(feature = "oslog", target_vendor = "apple"))]
+        FatCrabLogSink::OsLog => Box::new(
+            tracing_oslog::OsLogger::new(OSLOG_SUBSYSTEM, "default")
+                .log_timestamp(log_timestamp)
+                .log_level(log_level),
+        ),
+        #[cfg(not(all(feature = "oslog", target_vendor = "apple")))]
+        FatCrabLogSink::OsLog => return Ok(None),
+    };
+    Ok(Some(layer))
+}
+
+// Every sink shares the same level, timestamp and level options. Sinks are set up by the
+// first call only, later calls with the same sinks just change the level, so toggling debug
+// logging is safe. Fails when later calls ask for other sinks, or when the host app already
+// installed its own tracing subscriber.
+pub fn init_tracing(
+    level: FilterLevel,
+    log_timestamp: bool,
+    log_level: bool,
+    sinks: Vec<FatCrabLogSink>,
+) -> Result<(), FatCrabError> {
+    let mut log_filter = LOG_FILTER.lock().unwrap();
+    if let Some(log_filter) = log_filter.as_mut() {
+        if log_filter.sinks != sinks {
+            return Err(FatCrabError::Simple {
+                description: "Tracing is already set up with other sinks".to_string(),
+            });
+        }
+        log_filter.level = level.into();
+        return log_filter.reload();
+    }
+    if tracing::dispatcher::has_been_set() {
+        return Err(foreign_subscriber());
+    }
+
+    let mut layers: Vec<BoxedLayer> = vec![Box::new(DelegateLayer)];
+    let mut errors = Vec::new();
+    for sink in sinks.iter() {
+        match sink_layer(sink.clone(), log_timestamp, log_level) {
+            Ok(Some(layer)) => layers.push(layer),
+            Ok(None) => errors.push(
+                "OsLog sink is only available on Apple platforms with the oslog feature"
+                    .to_string(),
+            ),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    let level: LevelFilter = level.into();
+    let (filter, handle) = reload::Layer::new(EnvFilter::default().add_directive(level.into()));
+    let collector = tracing_subscriber::registry().with(filter).with(layers);
+    if tracing::subscriber::set_global_default(collector).is_err() {
+        return Err(foreign_subscriber());
+    }
+    *log_filter = Some(LogFilter {
+        handle,
+        sinks,
+        level,
+        directives: Vec::new(),
+    });
+    drop(log_filter);
+
+    // Only reported once the sinks that could be set up are in place
+    for error in errors {
+        tracing::warn!("{}", error);
+    }
+    Ok(())
+}
+
+// Init tracing for Apple unified logging system. Does not fail, to stay source compatible,
+// so a failure is logged to whichever subscriber is in place.
+pub fn init_tracing_for_oslog(level: FilterLevel, log_timestamp: bool, log_level: bool) {
+    if let Err(error) = init_tracing(level, log_timestamp, log_level, vec![FatCrabLogSink::OsLog]) {
+        tracing::warn!("{}", error);
+    }
+}
+
+pub fn set_log_level(level: FilterLevel) -> Result<(), FatCrabError> {
+    let mut log_filter = LOG_FILTER.lock().unwrap();
+    let Some(log_filter) = log_filter.as_mut() else {
+        return Err(tracing_not_initialized());
+    };
+    log_filter.level = level.into();
+    log_filter.reload()
+}
+
+// EnvFilter directives such as "fatcrab_trading::relay=debug", replacing the previous ones.
+// The level set by set_log_level applies to everything they do not match.
+pub fn set_log_directives(directives: Vec<String>) -> Result<(), FatCrabError> {
+    let directives = directives
+        .iter()
+        .map(|directive| Directive::from_str(directive))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| FatCrabError::Simple {
+            description: format!("Invalid log directive - {}", e),
+        })?;
+
+    let mut log_filter = LOG_FILTER.lock().unwrap();
+    let Some(log_filter) = log_filter.as_mut() else {
+        return Err(tracing_not_initialized());
+    };
+    log_filter.directives = directives;
+    log_filter.reload()
+}
+
+fn foreign_subscriber() -> FatCrabError {
+    FatCrabError::Simple {
+        description: "The host app already set a global tracing subscriber".to_string(),
+    }
+}
+
+fn tracing_not_initialized() -> FatCrabError {
+    FatCrabError::Simple {
+        description: "Tracing is not initialized".to_string(),
+    }
+}
@@ -1,5 +1,5 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::sync::Arc;
-use std::thread::spawn;
 
 pub use fatcrab_trading::maker::FatCrabMakerState;
 use fatcrab_trading::maker::{FatCrabMakerAccess, FatCrabMakerNotif, MakerBuy, MakerSell};
@@ -8,33 +8,408 @@ use fatcrab_trading::maker::{
     FatCrabMakerNotifPeerStruct as InnerMakerNotifPeerStruct,
 };
 use tokio::sync::mpsc;
+use tracing::{warn, Instrument};
 
+use crate::journal::FatCrabJournalEntry;
+use crate::logging::trade_span;
+use crate::nip65::spawn_connect_trade_relays;
+use crate::notif::{
+    offer_notif_key, peer_notif_key, FatCrabNotifBufferConfig, NotifDelegate, TradeNotif,
+};
 use crate::offer::FatCrabOfferEnvelope;
-use crate::order::FatCrabOrder;
+use crate::order::{FatCrabOrder, FatCrabOrderType};
 use crate::peer::FatCrabPeerEnvelope;
+use crate::persist::now_timestamp;
+use crate::trade::{FatCrabTrade, FatCrabTradeRole, FatCrabTradeState};
 use crate::trade_rsp::FatCrabTradeRspType;
+use crate::trader::TraderContext;
 use crate::types::FatCrabMakerNotifDelegate;
 use crate::{error::FatCrabError, RUNTIME};
 
+// Only sits between the Trading Engine and the hub, delegates are buffered as they register
 const MAKER_NOTIF_CHANNEL_SIZE: usize = 10;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FatCrabMakerAction {
+    PostNewOrder,
+    CancelOrder,
+    TradeResponse,
+    ReleaseNotifyPeer,
+    CheckBtcTxConfirmation,
+    NotifyPeer,
+    TradeComplete,
+}
+
+impl Display for FatCrabMakerAction {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let action_string = match self {
+            FatCrabMakerAction::PostNewOrder => "PostNewOrder",
+            FatCrabMakerAction::CancelOrder => "CancelOrder",
+            FatCrabMakerAction::TradeResponse => "TradeResponse",
+            FatCrabMakerAction::ReleaseNotifyPeer => "ReleaseNotifyPeer",
+            FatCrabMakerAction::CheckBtcTxConfirmation => "CheckBtcTxConfirmation",
+            FatCrabMakerAction::NotifyPeer => "NotifyPeer",
+            FatCrabMakerAction::TradeComplete => "TradeComplete",
+        };
+        write!(f, "{}", action_string)
+    }
+}
+
+pub(crate) fn maker_state_name(state: &FatCrabMakerState) -> &'static str {
+    match state {
+        FatCrabMakerState::New => "New",
+        FatCrabMakerState::WaitingForOffers => "WaitingForOffers",
+        FatCrabMakerState::ReceivedOffer => "ReceivedOffer",
+        FatCrabMakerState::AcceptedOffer => "AcceptedOffer",
+        FatCrabMakerState::InboundBtcNotified => "InboundBtcNotified",
+        FatCrabMakerState::InboundFcNotified => "InboundFcNotified",
+        FatCrabMakerState::NotifiedOutbound => "NotifiedOutbound",
+        FatCrabMakerState::TradeCompleted => "TradeCompleted",
+        FatCrabMakerState::TradeCancelled => "TradeCancelled",
+    }
+}
+
+// Buy Maker sends BTC first upon accepting an offer, then waits for the Fatcrab from the Taker
+fn buy_maker_allowed_actions(state: &FatCrabMakerState) -> Vec<FatCrabMakerAction> {
+    match state {
+        FatCrabMakerState::New => vec![
+            FatCrabMakerAction::PostNewOrder,
+            FatCrabMakerAction::CancelOrder,
+        ],
+        FatCrabMakerState::WaitingForOffers => vec![FatCrabMakerAction::CancelOrder],
+        FatCrabMakerState::ReceivedOffer => vec![
+            FatCrabMakerAction::TradeResponse,
+            FatCrabMakerAction::CancelOrder,
+        ],
+        FatCrabMakerState::AcceptedOffer => vec![FatCrabMakerAction::ReleaseNotifyPeer],
+        FatCrabMakerState::InboundFcNotified => vec![FatCrabMakerAction::TradeComplete],
+        FatCrabMakerState::InboundBtcNotified
+        | FatCrabMakerState::NotifiedOutbound
+        | FatCrabMakerState::TradeCompleted
+        | FatCrabMakerState::TradeCancelled => vec![],
+    }
+}
+
+// Sell Maker waits for BTC from the Taker, confirms it, then sends the Fatcrab
+fn sell_maker_allowed_actions(state: &FatCrabMakerState) -> Vec<FatCrabMakerAction> {
+    match state {
+        FatCrabMakerState::New => vec![
+            FatCrabMakerAction::PostNewOrder,
+            FatCrabMakerAction::CancelOrder,
+        ],
+        FatCrabMakerState::WaitingForOffers => vec![FatCrabMakerAction::CancelOrder],
+        FatCrabMakerState::ReceivedOffer => vec![
+            FatCrabMakerAction::TradeResponse,
+            FatCrabMakerAction::CancelOrder,
+        ],
+        FatCrabMakerState::InboundBtcNotified => vec![
+            FatCrabMakerAction::CheckBtcTxConfirmation,
+            FatCrabMakerAction::NotifyPeer,
+        ],
+        FatCrabMakerState::NotifiedOutbound => vec![
+            FatCrabMakerAction::CheckBtcTxConfirmation,
+            FatCrabMakerAction::TradeComplete,
+        ],
+        FatCrabMakerState::AcceptedOffer
+        | FatCrabMakerState::InboundFcNotified
+        | FatCrabMakerState::TradeCompleted
+        | FatCrabMakerState::TradeCancelled => vec![],
+    }
+}
+
+fn check_maker_action(
+    allowed_actions: Vec<FatCrabMakerAction>,
+    state: &FatCrabMakerState,
+    action: FatCrabMakerAction,
+) -> Result<(), FatCrabError> {
+    if allowed_actions.contains(&action) {
+        return Ok(());
+    }
+    Err(FatCrabError::InvalidStateTransition {
+        from: maker_state_name(state).to_string(),
+        action: action.to_string(),
+    })
+}
+
+fn journal_maker_transition(
+    ctx: &TraderContext,
+    trade_uuid: &str,
+    trigger: impl ToString,
+    from: Option<&FatCrabMakerState>,
+    result: &Result<FatCrabMakerState, FatCrabError>,
+) {
+    let from = from.map(maker_state_name);
+    let entry = match result {
+        Ok(to) => {
+            ctx.trade_timestamps.touch(trade_uuid, maker_state_name(to));
+            FatCrabJournalEntry::new(trigger, from, Some(maker_state_name(to)), None)
+        }
+        Err(error) => FatCrabJournalEntry::new(trigger, from, None, Some(error)),
+    };
+    ctx.trade_journal.append(trade_uuid, entry);
+}
+
+fn journal_maker_notif(
+    ctx: &TraderContext,
+    trade_uuid: &str,
+    trigger: &str,
+    to: &FatCrabMakerState,
+) {
+    let from = ctx.trade_journal.last_state(trade_uuid);
+    let to = maker_state_name(to);
+    ctx.trade_timestamps.touch(trade_uuid, to);
+    let entry = FatCrabJournalEntry::new(trigger, from.as_deref(), Some(to), None);
+    ctx.trade_journal.append(trade_uuid, entry);
+}
+
+fn spawn_maker_notif_forwarder(
+    ctx: Arc<TraderContext>,
+    trade_uuid: String,
+    order_type: FatCrabOrderType,
+    mut rx: mpsc::Receiver<FatCrabMakerNotif>,
+) {
+    let span = trade_span(&trade_uuid);
+    let forward = async move {
+        while let Some(notif) = rx.recv().await {
+            // Publishing waits for room in the delegate's buffer under the Block policy
+            match notif {
+                FatCrabMakerNotif::Offer(offer_notif) => {
+                    journal_maker_notif(&ctx, &trade_uuid, "OfferReceived", &offer_notif.state);
+                    let offer_envelope = FatCrabOfferEnvelope::from(offer_notif.offer_envelope);
+                    let key = offer_notif_key(&offer_envelope);
+                    ctx.notif_hub
+                        .publish(&trade_uuid, key, |seq| {
+                            TradeNotif::MakerOffer(FatCrabMakerNotifOfferStruct::new(
+                                trade_uuid.clone(),
+                                order_type.clone(),
+                                seq,
+                                offer_notif.state,
+                                Arc::new(offer_envelope),
+                            ))
+                        })
+                        .await;
+                }
+                FatCrabMakerNotif::Peer(peer_notif) => {
+                    journal_maker_notif(&ctx, &trade_uuid, "PeerMessage", &peer_notif.state);
+                    let peer_envelope = FatCrabPeerEnvelope::from(peer_notif.peer_envelope);
+                    let key = peer_notif_key(&peer_envelope);
+                    ctx.notif_hub
+                        .publish(&trade_uuid, key, |seq| {
+                            TradeNotif::MakerPeer(FatCrabMakerNotifPeerStruct::new(
+                                trade_uuid.clone(),
+                                order_type.clone(),
+                                seq,
+                                peer_notif.state,
+                                Arc::new(peer_envelope),
+                            ))
+                        })
+                        .await;
+                }
+            }
+        }
+    };
+    RUNTIME.spawn(forward.instrument(span));
+}
+
+// The state a notification moved the trade to follows from the notification itself,
+// the trade may well have moved on since it arrived
+fn maker_peer_notif_state(order_type: &FatCrabOrderType) -> FatCrabMakerState {
+    match order_type {
+        FatCrabOrderType::Buy => FatCrabMakerState::InboundFcNotified,
+        FatCrabOrderType::Sell => FatCrabMakerState::InboundBtcNotified,
+    }
+}
+
+fn replay_maker_notifs(
+    ctx: &TraderContext,
+    trade_uuid: &str,
+    order_type: FatCrabOrderType,
+    state: FatCrabMakerState,
+    offer_envelopes: Vec<Arc<FatCrabOfferEnvelope>>,
+    peer_envelope: Option<Arc<FatCrabPeerEnvelope>>,
+) {
+    // Transitions made while the app was not running only show up as a different state
+    if ctx.trade_journal.last_state(trade_uuid).as_deref() != Some(maker_state_name(&state)) {
+        journal_maker_notif(ctx, trade_uuid, "Recovered", &state);
+    }
+    let trade_state: FatCrabTradeState = (&state).into();
+    if matches!(
+        trade_state,
+        FatCrabTradeState::Completed | FatCrabTradeState::Cancelled
+    ) {
+        ctx.notif_hub.trade_ended(trade_uuid);
+        return;
+    }
+
+    for offer_envelope in offer_envelopes {
+        let key = offer_notif_key(&offer_envelope);
+        ctx.notif_hub.replay(trade_uuid, key, |seq| {
+            TradeNotif::MakerOffer(FatCrabMakerNotifOfferStruct::new(
+                trade_uuid.to_string(),
+                order_type.clone(),
+                seq,
+                FatCrabMakerState::ReceivedOffer,
+                offer_envelope,
+            ))
+        });
+    }
+    if let Some(peer_envelope) = peer_envelope {
+        let key = peer_notif_key(&peer_envelope);
+        let state = maker_peer_notif_state(&order_type);
+        ctx.notif_hub.replay(trade_uuid, key, |seq| {
+            TradeNotif::MakerPeer(FatCrabMakerNotifPeerStruct::new(
+                trade_uuid.to_string(),
+                order_type,
+                seq,
+                state,
+                peer_envelope,
+            ))
+        });
+    }
+}
+
 pub struct FatCrabBuyMaker {
     inner: FatCrabMakerAccess<MakerBuy>,
+    trade_uuid: String,
+    ctx: Arc<TraderContext>,
 }
 
 pub struct FatCrabSellMaker {
     inner: FatCrabMakerAccess<MakerSell>,
+    trade_uuid: String,
+    ctx: Arc<TraderContext>,
 }
 
 impl FatCrabBuyMaker {
-    pub(crate) fn new(maker: FatCrabMakerAccess<MakerBuy>) -> Self {
-        Self { inner: maker }
+    pub(crate) fn new(
+        maker: FatCrabMakerAccess<MakerBuy>,
+        trade_uuid: String,
+        ctx: Arc<TraderContext>,
+    ) -> Self {
+        Self {
+            inner: maker,
+            trade_uuid,
+            ctx,
+        }
+    }
+
+    // Notifications are captured from the moment the Trader hands out a trade, delegate or not
+    pub(crate) fn attach_notifs(&self) {
+        let _span = trade_span(&self.trade_uuid).entered();
+        if !self.ctx.notif_hub.begin_attach(&self.trade_uuid) {
+            return;
+        }
+        let (tx, rx) = mpsc::channel(MAKER_NOTIF_CHANNEL_SIZE);
+        if let Err(error) = RUNTIME.block_on(async { self.inner.register_notif_tx(tx).await }) {
+            warn!(
+                "Failed to capture notifications for trade {} - {}",
+                self.trade_uuid,
+                FatCrabError::from(error)
+            );
+            self.ctx.notif_hub.abort_attach(&self.trade_uuid);
+            return;
+        }
+        spawn_maker_notif_forwarder(
+            self.ctx.clone(),
+            self.trade_uuid.clone(),
+            FatCrabOrderType::Buy,
+            rx,
+        );
+
+        // Whatever arrived while the app was not running is only known to the Trading Engine
+        match (self.get_state(), self.query_offers(), self.query_peer_msg()) {
+            (Ok(state), Ok(offer_envelopes), Ok(peer_envelope)) => replay_maker_notifs(
+                &self.ctx,
+                &self.trade_uuid,
+                FatCrabOrderType::Buy,
+                state,
+                offer_envelopes,
+                peer_envelope,
+            ),
+            _ => warn!(
+                "Failed to recover notifications for trade {}",
+                self.trade_uuid
+            ),
+        }
+
+        // Restored trades past the offer stage reach their peer on its own relays again
+        if let Ok(Some(peer_pubkey)) = self.get_peer_pubkey() {
+            spawn_connect_trade_relays(self.ctx.clone(), self.trade_uuid.clone(), peer_pubkey);
+        }
+    }
+
+    pub fn allowed_actions(&self) -> Result<Vec<FatCrabMakerAction>, FatCrabError> {
+        let state = self.get_state()?;
+        Ok(buy_maker_allowed_actions(&state))
+    }
+
+    fn check_action(&self, action: FatCrabMakerAction) -> Result<FatCrabMakerState, FatCrabError> {
+        let state = self.get_state()?;
+        if let Err(error) = check_maker_action(buy_maker_allowed_actions(&state), &state, action) {
+            let from = maker_state_name(&state);
+            let entry = FatCrabJournalEntry::new(action, Some(from), None, Some(&error));
+            self.ctx.trade_journal.append(&self.trade_uuid, entry);
+            return Err(error);
+        }
+        Ok(state)
+    }
+
+    fn journal_action(
+        &self,
+        action: FatCrabMakerAction,
+        from: &FatCrabMakerState,
+        result: Result<FatCrabMakerState, FatCrabError>,
+    ) -> Result<FatCrabMakerState, FatCrabError> {
+        journal_maker_transition(&self.ctx, &self.trade_uuid, action, Some(from), &result);
+        result
+    }
+
+    pub fn get_trade_journal(&self) -> Result<Vec<FatCrabJournalEntry>, FatCrabError> {
+        self.ctx.trade_journal.entries(&self.trade_uuid)
+    }
+
+    fn record_history(&self, state: &FatCrabMakerState) -> Result<(), FatCrabError> {
+        let trade_state: FatCrabTradeState = state.into();
+        if !matches!(
+            trade_state,
+            FatCrabTradeState::Completed | FatCrabTradeState::Cancelled
+        ) {
+            return Ok(());
+        }
+        self.ctx.notif_hub.trade_ended(&self.trade_uuid);
+        if let Some(peer_envelope) = self.query_peer_msg()? {
+            self.ctx
+                .trade_history
+                .observe_fatcrab_txid(&self.trade_uuid, peer_envelope.message().txid);
+        }
+        let trade = self.make_trade(state)?;
+        self.ctx.trade_history.record(&trade);
+        Ok(())
+    }
+
+    // As listed by the Trader
+    pub(crate) fn trade(&self) -> Result<FatCrabTrade, FatCrabError> {
+        let state = self.get_state()?;
+        self.make_trade(&state)
+    }
+
+    fn make_trade(&self, state: &FatCrabMakerState) -> Result<FatCrabTrade, FatCrabError> {
+        Ok(self.ctx.trade_timestamps.make_trade(
+            self.trade_uuid.clone(),
+            FatCrabTradeRole::Maker,
+            self.get_order_details()?,
+            state.into(),
+            self.get_peer_pubkey()?,
+        ))
     }
 
     pub fn post_new_order(&self) -> Result<FatCrabMakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabMakerAction::PostNewOrder)?;
+        let result = RUNTIME
             .block_on(async { self.inner.post_new_order().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        self.journal_action(FatCrabMakerAction::PostNewOrder, &from, result)
     }
 
     pub fn get_order_details(&self) -> Result<FatCrabOrder, FatCrabError> {
@@ -91,9 +466,19 @@ impl FatCrabBuyMaker {
     }
 
     pub fn cancel_order(&self) -> Result<FatCrabMakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabMakerAction::CancelOrder)?;
+        let result = RUNTIME
             .block_on(async { self.inner.cancel_order().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        let state = self.journal_action(FatCrabMakerAction::CancelOrder, &from, result)?;
+        if let Err(error) = self.record_history(&state) {
+            warn!(
+                "Failed to record history for trade {} - {}",
+                self.trade_uuid, error
+            );
+        }
+        Ok(state)
     }
 
     pub fn trade_response(
@@ -101,72 +486,220 @@ impl FatCrabBuyMaker {
         trade_rsp_type: FatCrabTradeRspType,
         offer_envelope: Arc<FatCrabOfferEnvelope>,
     ) -> Result<FatCrabMakerState, FatCrabError> {
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabMakerAction::TradeResponse)?;
+        let accepted = matches!(trade_rsp_type, FatCrabTradeRspType::Accept);
+        let peer_pubkey = offer_envelope.pubkey();
         let offer_envelope = offer_envelope.as_ref().clone();
-        RUNTIME
+        let result = RUNTIME
             .block_on(async {
                 self.inner
                     .trade_response(trade_rsp_type, offer_envelope.into())
                     .await
             })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        let state = self.journal_action(FatCrabMakerAction::TradeResponse, &from, result)?;
+        if accepted {
+            spawn_connect_trade_relays(self.ctx.clone(), self.trade_uuid.clone(), peer_pubkey);
+        }
+        Ok(state)
     }
 
     pub fn release_notify_peer(&self) -> Result<FatCrabMakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabMakerAction::ReleaseNotifyPeer)?;
+        let result = RUNTIME
             .block_on(async { self.inner.release_notify_peer().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        self.journal_action(FatCrabMakerAction::ReleaseNotifyPeer, &from, result)
     }
 
     pub fn trade_complete(&self) -> Result<FatCrabMakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabMakerAction::TradeComplete)?;
+        let result = RUNTIME
             .block_on(async { self.inner.trade_complete().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        let state = self.journal_action(FatCrabMakerAction::TradeComplete, &from, result)?;
+        if let Err(error) = self.record_history(&state) {
+            warn!(
+                "Failed to record history for trade {} - {}",
+                self.trade_uuid, error
+            );
+        }
+        Ok(state)
     }
 
     pub fn register_notif_delegate(
         &self,
         delegate: Arc<dyn FatCrabMakerNotifDelegate>,
+        buffer_config: Option<FatCrabNotifBufferConfig>,
     ) -> Result<(), FatCrabError> {
-        RUNTIME.block_on(async {
-            _ = self.inner.unregister_notif_tx().await;
-        });
-
-        let (tx, mut rx) = mpsc::channel(MAKER_NOTIF_CHANNEL_SIZE);
-        spawn(move || loop {
-            match rx.blocking_recv() {
-                Some(notif) => match notif {
-                    FatCrabMakerNotif::Offer(offer_notif) => {
-                        delegate.on_maker_offer_notif(offer_notif.into());
-                    }
-                    FatCrabMakerNotif::Peer(peer_notif) => {
-                        delegate.on_maker_peer_notif(peer_notif.into());
-                    }
-                },
-                None => break,
-            }
-        });
-
-        RUNTIME
-            .block_on(async { self.inner.register_notif_tx(tx).await })
-            .map_err(|e| e.into())
+        self.attach_notifs();
+        self.ctx.notif_hub.register(
+            &self.trade_uuid,
+            NotifDelegate::Maker(delegate),
+            buffer_config.unwrap_or_default(),
+        );
+        Ok(())
     }
 
     pub fn unregister_notif_delegate(&self) -> Result<(), FatCrabError> {
-        RUNTIME
-            .block_on(async { self.inner.unregister_notif_tx().await })
-            .map_err(|e| e.into())
+        self.ctx.notif_hub.unregister(&self.trade_uuid);
+        Ok(())
+    }
+
+    pub fn acknowledge_notifs(&self, seq: u64) -> Result<(), FatCrabError> {
+        self.ctx.notif_hub.acknowledge(&self.trade_uuid, seq)
     }
 }
 
 impl FatCrabSellMaker {
-    pub(crate) fn new(maker: FatCrabMakerAccess<MakerSell>) -> Self {
-        Self { inner: maker }
+    pub(crate) fn new(
+        maker: FatCrabMakerAccess<MakerSell>,
+        trade_uuid: String,
+        ctx: Arc<TraderContext>,
+    ) -> Self {
+        Self {
+            inner: maker,
+            trade_uuid,
+            ctx,
+        }
+    }
+
+    // Notifications are captured from the moment the Trader hands out a trade, delegate or not
+    pub(crate) fn attach_notifs(&self) {
+        let _span = trade_span(&self.trade_uuid).entered();
+        if !self.ctx.notif_hub.begin_attach(&self.trade_uuid) {
+            return;
+        }
+        let (tx, rx) = mpsc::channel(MAKER_NOTIF_CHANNEL_SIZE);
+        if let Err(error) = RUNTIME.block_on(async { self.inner.register_notif_tx(tx).await }) {
+            warn!(
+                "Failed to capture notifications for trade {} - {}",
+                self.trade_uuid,
+                FatCrabError::from(error)
+            );
+            self.ctx.notif_hub.abort_attach(&self.trade_uuid);
+            return;
+        }
+        spawn_maker_notif_forwarder(
+            self.ctx.clone(),
+            self.trade_uuid.clone(),
+            FatCrabOrderType::Sell,
+            rx,
+        );
+
+        // Whatever arrived while the app was not running is only known to the Trading Engine
+        match (self.get_state(), self.query_offers(), self.query_peer_msg()) {
+            (Ok(state), Ok(offer_envelopes), Ok(peer_envelope)) => replay_maker_notifs(
+                &self.ctx,
+                &self.trade_uuid,
+                FatCrabOrderType::Sell,
+                state,
+                offer_envelopes,
+                peer_envelope,
+            ),
+            _ => warn!(
+                "Failed to recover notifications for trade {}",
+                self.trade_uuid
+            ),
+        }
+
+        // Restored trades past the offer stage reach their peer on its own relays again
+        if let Ok(Some(peer_pubkey)) = self.get_peer_pubkey() {
+            spawn_connect_trade_relays(self.ctx.clone(), self.trade_uuid.clone(), peer_pubkey);
+        }
+    }
+
+    pub fn allowed_actions(&self) -> Result<Vec<FatCrabMakerAction>, FatCrabError> {
+        let state = self.get_state()?;
+        Ok(sell_maker_allowed_actions(&state))
+    }
+
+    fn check_action(&self, action: FatCrabMakerAction) -> Result<FatCrabMakerState, FatCrabError> {
+        let state = self.get_state()?;
+        if let Err(error) = check_maker_action(sell_maker_allowed_actions(&state), &state, action) {
+            let from = maker_state_name(&state);
+            let entry = FatCrabJournalEntry::new(action, Some(from), None, Some(&error));
+            self.ctx.trade_journal.append(&self.trade_uuid, entry);
+            return Err(error);
+        }
+        Ok(state)
+    }
+
+    fn journal_action(
+        &self,
+        action: FatCrabMakerAction,
+        from: &FatCrabMakerState,
+        result: Result<FatCrabMakerState, FatCrabError>,
+    ) -> Result<FatCrabMakerState, FatCrabError> {
+        journal_maker_transition(&self.ctx, &self.trade_uuid, action, Some(from), &result);
+        result
+    }
+
+    pub fn get_trade_journal(&self) -> Result<Vec<FatCrabJournalEntry>, FatCrabError> {
+        self.ctx.trade_journal.entries(&self.trade_uuid)
+    }
+
+    fn record_history(&self, state: &FatCrabMakerState) -> Result<(), FatCrabError> {
+        let trade_state: FatCrabTradeState = state.into();
+        if !matches!(
+            trade_state,
+            FatCrabTradeState::Completed | FatCrabTradeState::Cancelled
+        ) {
+            return Ok(());
+        }
+        self.ctx.notif_hub.trade_ended(&self.trade_uuid);
+        if let Some(btc_txid) = self.get_peer_btc_txid()? {
+            self.ctx
+                .trade_history
+                .observe_peer_btc_txid(&self.trade_uuid, btc_txid);
+            self.observe_btc_confirmations();
+        }
+        let trade = self.make_trade(state)?;
+        self.ctx.trade_history.record(&trade);
+        Ok(())
+    }
+
+    // Read once the trade has ended, so check_action is not asked
+    fn observe_btc_confirmations(&self) {
+        match RUNTIME.block_on(async { self.inner.check_btc_tx_confirmation().await }) {
+            Ok(confirmations) => self
+                .ctx
+                .trade_history
+                .observe_btc_confirmations(&self.trade_uuid, confirmations),
+            Err(error) => warn!(
+                "Failed to read BTC confirmations for trade {} - {}",
+                self.trade_uuid,
+                FatCrabError::from(error)
+            ),
+        }
+    }
+
+    // As listed by the Trader
+    pub(crate) fn trade(&self) -> Result<FatCrabTrade, FatCrabError> {
+        let state = self.get_state()?;
+        self.make_trade(&state)
+    }
+
+    fn make_trade(&self, state: &FatCrabMakerState) -> Result<FatCrabTrade, FatCrabError> {
+        Ok(self.ctx.trade_timestamps.make_trade(
+            self.trade_uuid.clone(),
+            FatCrabTradeRole::Maker,
+            self.get_order_details()?,
+            state.into(),
+            self.get_peer_pubkey()?,
+        ))
     }
 
     pub fn post_new_order(&self) -> Result<FatCrabMakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabMakerAction::PostNewOrder)?;
+        let result = RUNTIME
             .block_on(async { self.inner.post_new_order().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        self.journal_action(FatCrabMakerAction::PostNewOrder, &from, result)
     }
 
     pub fn get_order_details(&self) -> Result<FatCrabOrder, FatCrabError> {
@@ -229,9 +762,19 @@ impl FatCrabSellMaker {
     }
 
     pub fn cancel_order(&self) -> Result<FatCrabMakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabMakerAction::CancelOrder)?;
+        let result = RUNTIME
             .block_on(async { self.inner.cancel_order().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        let state = self.journal_action(FatCrabMakerAction::CancelOrder, &from, result)?;
+        if let Err(error) = self.record_history(&state) {
+            warn!(
+                "Failed to record history for trade {} - {}",
+                self.trade_uuid, error
+            );
+        }
+        Ok(state)
     }
 
     pub fn trade_response(
@@ -239,79 +782,111 @@ impl FatCrabSellMaker {
         trade_rsp_type: FatCrabTradeRspType,
         offer_envelope: Arc<FatCrabOfferEnvelope>,
     ) -> Result<FatCrabMakerState, FatCrabError> {
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabMakerAction::TradeResponse)?;
+        let accepted = matches!(trade_rsp_type, FatCrabTradeRspType::Accept);
+        let peer_pubkey = offer_envelope.pubkey();
         let offer_envelope = offer_envelope.as_ref().clone();
-        RUNTIME
+        let result = RUNTIME
             .block_on(async {
                 self.inner
                     .trade_response(trade_rsp_type, offer_envelope.into())
                     .await
             })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        let state = self.journal_action(FatCrabMakerAction::TradeResponse, &from, result)?;
+        if accepted {
+            spawn_connect_trade_relays(self.ctx.clone(), self.trade_uuid.clone(), peer_pubkey);
+        }
+        Ok(state)
     }
 
     pub fn check_btc_tx_confirmation(&self) -> Result<u32, FatCrabError> {
+        let _span = trade_span(&self.trade_uuid).entered();
+        self.check_action(FatCrabMakerAction::CheckBtcTxConfirmation)?;
         RUNTIME
             .block_on(async { self.inner.check_btc_tx_confirmation().await })
             .map_err(|e| e.into())
     }
 
     pub fn notify_peer(&self, fatcrab_txid: String) -> Result<FatCrabMakerState, FatCrabError> {
-        RUNTIME
-            .block_on(async { self.inner.notify_peer(fatcrab_txid).await })
-            .map_err(|e| e.into())
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabMakerAction::NotifyPeer)?;
+        let result = RUNTIME
+            .block_on(async { self.inner.notify_peer(fatcrab_txid.clone()).await })
+            .map_err(|e| e.into());
+        let state = self.journal_action(FatCrabMakerAction::NotifyPeer, &from, result)?;
+        self.ctx
+            .trade_history
+            .observe_fatcrab_txid(&self.trade_uuid, fatcrab_txid);
+        Ok(state)
     }
 
     pub fn trade_complete(&self) -> Result<FatCrabMakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabMakerAction::TradeComplete)?;
+        let result = RUNTIME
             .block_on(async { self.inner.trade_complete().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        let state = self.journal_action(FatCrabMakerAction::TradeComplete, &from, result)?;
+        if let Err(error) = self.record_history(&state) {
+            warn!(
+                "Failed to record history for trade {} - {}",
+                self.trade_uuid, error
+            );
+        }
+        Ok(state)
     }
 
     pub fn register_notif_delegate(
         &self,
         delegate: Arc<dyn FatCrabMakerNotifDelegate>,
+        buffer_config: Option<FatCrabNotifBufferConfig>,
     ) -> Result<(), FatCrabError> {
-        RUNTIME.block_on(async {
-            _ = self.inner.unregister_notif_tx().await;
-        });
-
-        let (tx, mut rx) = mpsc::channel(MAKER_NOTIF_CHANNEL_SIZE);
-        spawn(move || loop {
-            match rx.blocking_recv() {
-                Some(notif) => match notif {
-                    FatCrabMakerNotif::Offer(offer_notif) => {
-                        delegate.on_maker_offer_notif(offer_notif.into());
-                    }
-                    FatCrabMakerNotif::Peer(peer_notif) => {
-                        delegate.on_maker_peer_notif(peer_notif.into());
-                    }
-                },
-                None => break,
-            }
-        });
-
-        RUNTIME
-            .block_on(async { self.inner.register_notif_tx(tx).await })
-            .map_err(|e| e.into())
+        self.attach_notifs();
+        self.ctx.notif_hub.register(
+            &self.trade_uuid,
+            NotifDelegate::Maker(delegate),
+            buffer_config.unwrap_or_default(),
+        );
+        Ok(())
     }
 
     pub fn unregister_notif_delegate(&self) -> Result<(), FatCrabError> {
-        RUNTIME
-            .block_on(async { self.inner.unregister_notif_tx().await })
-            .map_err(|e| e.into())
+        self.ctx.notif_hub.unregister(&self.trade_uuid);
+        Ok(())
+    }
+
+    pub fn acknowledge_notifs(&self, seq: u64) -> Result<(), FatCrabError> {
+        self.ctx.notif_hub.acknowledge(&self.trade_uuid, seq)
     }
 }
 
+#[derive(Clone)]
 pub struct FatCrabMakerNotifOfferStruct {
+    pub trade_uuid: String,
+    pub order_type: FatCrabOrderType,
+    pub seq: u64,
+    pub timestamp: u64, // when the FFI captured it, in seconds since the Unix epoch
     pub state: FatCrabMakerState,
     pub offer_envelope: Arc<FatCrabOfferEnvelope>,
 }
 
-impl From<InnerMakerNotifOfferStruct> for FatCrabMakerNotifOfferStruct {
-    fn from(offer_notif: InnerMakerNotifOfferStruct) -> Self {
+impl FatCrabMakerNotifOfferStruct {
+    pub(crate) fn new(
+        trade_uuid: String,
+        order_type: FatCrabOrderType,
+        seq: u64,
+        state: FatCrabMakerState,
+        offer_envelope: Arc<FatCrabOfferEnvelope>,
+    ) -> Self {
         Self {
-            state: offer_notif.state,
-            offer_envelope: Arc::new(offer_notif.offer_envelope.into()),
+            trade_uuid,
+            order_type,
+            seq,
+            timestamp: now_timestamp(),
+            state,
+            offer_envelope,
         }
     }
 }
@@ -325,16 +900,31 @@ impl Into<InnerMakerNotifOfferStruct> for FatCrabMakerNotifOfferStruct {
     }
 }
 
+#[derive(Clone)]
 pub struct FatCrabMakerNotifPeerStruct {
+    pub trade_uuid: String,
+    pub order_type: FatCrabOrderType,
+    pub seq: u64,
+    pub timestamp: u64, // when the FFI captured it, in seconds since the Unix epoch
     pub state: FatCrabMakerState,
     pub peer_envelope: Arc<FatCrabPeerEnvelope>,
 }
 
-impl From<InnerMakerNotifPeerStruct> for FatCrabMakerNotifPeerStruct {
-    fn from(peer_notif: InnerMakerNotifPeerStruct) -> Self {
+impl FatCrabMakerNotifPeerStruct {
+    pub(crate) fn new(
+        trade_uuid: String,
+        order_type: FatCrabOrderType,
+        seq: u64,
+        state: FatCrabMakerState,
+        peer_envelope: Arc<FatCrabPeerEnvelope>,
+    ) -> Self {
         Self {
-            state: peer_notif.state,
-            peer_envelope: Arc::new(peer_notif.peer_envelope.into()),
+            trade_uuid,
+            order_type,
+            seq,
+            timestamp: now_timestamp(),
+            state,
+            peer_envelope,
         }
     }
 }
@@ -347,3 +937,105 @@ impl Into<InnerMakerNotifPeerStruct> for FatCrabMakerNotifPeerStruct {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use FatCrabMakerAction::*;
+
+    const ALL_ACTIONS: [FatCrabMakerAction; 7] = [
+        PostNewOrder,
+        CancelOrder,
+        TradeResponse,
+        ReleaseNotifyPeer,
+        CheckBtcTxConfirmation,
+        NotifyPeer,
+        TradeComplete,
+    ];
+
+    #[test]
+    fn buy_maker_allowed_actions_follow_the_trade() {
+        let table = [
+            (FatCrabMakerState::New, vec![PostNewOrder, CancelOrder]),
+            (FatCrabMakerState::WaitingForOffers, vec![CancelOrder]),
+            (
+                FatCrabMakerState::ReceivedOffer,
+                vec![TradeResponse, CancelOrder],
+            ),
+            (FatCrabMakerState::AcceptedOffer, vec![ReleaseNotifyPeer]),
+            (FatCrabMakerState::InboundBtcNotified, vec![]),
+            (FatCrabMakerState::InboundFcNotified, vec![TradeComplete]),
+            (FatCrabMakerState::NotifiedOutbound, vec![]),
+            (FatCrabMakerState::TradeCompleted, vec![]),
+            (FatCrabMakerState::TradeCancelled, vec![]),
+        ];
+        for (state, expected) in table {
+            assert_eq!(
+                buy_maker_allowed_actions(&state),
+                expected,
+                "{}",
+                maker_state_name(&state)
+            );
+        }
+    }
+
+    #[test]
+    fn sell_maker_allowed_actions_follow_the_trade() {
+        let table = [
+            (FatCrabMakerState::New, vec![PostNewOrder, CancelOrder]),
+            (FatCrabMakerState::WaitingForOffers, vec![CancelOrder]),
+            (
+                FatCrabMakerState::ReceivedOffer,
+                vec![TradeResponse, CancelOrder],
+            ),
+            (FatCrabMakerState::AcceptedOffer, vec![]),
+            (
+                FatCrabMakerState::InboundBtcNotified,
+                vec![CheckBtcTxConfirmation, NotifyPeer],
+            ),
+            (FatCrabMakerState::InboundFcNotified, vec![]),
+            (
+                FatCrabMakerState::NotifiedOutbound,
+                vec![CheckBtcTxConfirmation, TradeComplete],
+            ),
+            (FatCrabMakerState::TradeCompleted, vec![]),
+            (FatCrabMakerState::TradeCancelled, vec![]),
+        ];
+        for (state, expected) in table {
+            assert_eq!(
+                sell_maker_allowed_actions(&state),
+                expected,
+                "{}",
+                maker_state_name(&state)
+            );
+        }
+    }
+
+    #[test]
+    fn check_maker_action_only_allows_listed_actions() {
+        let state = FatCrabMakerState::ReceivedOffer;
+        for action in ALL_ACTIONS {
+            let result = check_maker_action(buy_maker_allowed_actions(&state), &state, action);
+            assert_eq!(
+                result.is_ok(),
+                action == TradeResponse || action == CancelOrder,
+                "{}",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn check_maker_action_names_state_and_action() {
+        let state = FatCrabMakerState::WaitingForOffers;
+        let result = check_maker_action(sell_maker_allowed_actions(&state), &state, TradeComplete);
+        match result {
+            Err(FatCrabError::InvalidStateTransition { from, action }) => {
+                assert_eq!(from, "WaitingForOffers");
+                assert_eq!(action, "TradeComplete");
+            }
+            _ => panic!("TradeComplete allowed while waiting for offers"),
+        }
+    }
+}
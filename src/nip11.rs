@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use nostr_sdk::nips::nip11;
+use tracing::warn;
+use url::Url;
+
+use crate::error::FatCrabError;
+use crate::types::RelayInformationDocument;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FatCrabRelayPolicyAction {
+    Allow,
+    Flag,
+    Reject,
+}
+
+#[derive(Clone)]
+pub struct FatCrabRelayRequirements {
+    // Relays not listing every one of these in their NIP-11 document are rejected
+    pub required_nips: Vec<u16>,
+    pub paid: FatCrabRelayPolicyAction,
+    pub restricted: FatCrabRelayPolicyAction,
+}
+
+impl Default for FatCrabRelayRequirements {
+    fn default() -> Self {
+        Self {
+            required_nips: Vec::new(),
+            paid: FatCrabRelayPolicyAction::Allow,
+            restricted: FatCrabRelayPolicyAction::Allow,
+        }
+    }
+}
+
+impl FatCrabRelayRequirements {
+    fn is_default(&self) -> bool {
+        self.required_nips.is_empty()
+            && self.paid == FatCrabRelayPolicyAction::Allow
+            && self.restricted == FatCrabRelayPolicyAction::Allow
+    }
+}
+
+#[derive(Clone)]
+pub struct FatCrabRelayProbe {
+    pub url: String,
+    // Whether the relay answered with its NIP-11 document
+    pub reachable: bool,
+    pub latency_ms: Option<u64>,
+    pub document: Option<RelayInformationDocument>,
+    pub payment_required: bool,
+    pub auth_required: bool,
+    pub restricted_writes: bool,
+    // Why the relay does not meet the current requirements, empty if it does
+    pub issues: Vec<String>,
+    pub rejected: bool,
+}
+
+fn document_from_nip11(document: nip11::RelayInformationDocument) -> RelayInformationDocument {
+    RelayInformationDocument {
+        name: document.name,
+        description: document.description,
+        pubkey: document.pubkey,
+        contact: document.contact,
+        supported_nips: document.supported_nips,
+        software: document.software,
+        version: document.version,
+        relay_countries: document.relay_countries,
+        language_tags: document.language_tags,
+        tags: document.tags,
+        posting_policy: document.posting_policy,
+        payments_url: document.payments_url,
+        icon: document.icon,
+    }
+}
+
+// The Trading Engine adds any relay it is given, so the NIP-11 document is
+// fetched and checked by the FFI before the relay is handed over
+pub(crate) struct RelayChecker {
+    requirements: Mutex<FatCrabRelayRequirements>,
+    probes: Mutex<HashMap<String, FatCrabRelayProbe>>,
+}
+
+impl RelayChecker {
+    pub(crate) fn new() -> Self {
+        Self {
+            requirements: Mutex::new(FatCrabRelayRequirements::default()),
+            probes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn set_requirements(&self, requirements: FatCrabRelayRequirements) {
+        *self.requirements.lock().unwrap() = requirements;
+    }
+
+    pub(crate) async fn probe(&self, url: &Url) -> FatCrabRelayProbe {
+        let started_at = Instant::now();
+        let result = nip11::RelayInformationDocument::get(url.clone(), None).await;
+        let latency_ms = started_at.elapsed().as_millis() as u64;
+
+        let mut probe = FatCrabRelayProbe {
+            url: url.to_string(),
+            reachable: false,
+            latency_ms: None,
+            document: None,
+            payment_required: false,
+            auth_required: false,
+            restricted_writes: false,
+            issues: Vec::new(),
+            rejected: false,
+        };
+        match result {
+            Ok(document) => {
+                if let Some(limitation) = &document.limitation {
+                    probe.payment_required = limitation.payment_required.unwrap_or(false);
+                    probe.auth_required = limitation.auth_required.unwrap_or(false);
+                    probe.restricted_writes = limitation.restricted_writes.unwrap_or(false);
+                }
+                probe.payment_required |= document.payments_url.is_some();
+                probe.reachable = true;
+                probe.latency_ms = Some(latency_ms);
+                probe.document = Some(document_from_nip11(document));
+            }
+            Err(error) => warn!("Relay {} unreachable - {}", url, error),
+        }
+
+        let requirements = self.requirements.lock().unwrap().clone();
+        self.evaluate(&mut probe, &requirements);
+        self.probes
+            .lock()
+            .unwrap()
+            .insert(probe.url.clone(), probe.clone());
+        probe
+    }
+
+    fn evaluate(&self, probe: &mut FatCrabRelayProbe, requirements: &FatCrabRelayRequirements) {
+        // Without its document there is no telling whether a relay is paid, restricted or
+        // lists the required NIPs, so it fails every requirement that is not allowed outright
+        if !probe.reachable {
+            let actions = [requirements.paid, requirements.restricted];
+            let reject = !requirements.required_nips.is_empty()
+                || actions.contains(&FatCrabRelayPolicyAction::Reject);
+            if reject || actions.contains(&FatCrabRelayPolicyAction::Flag) {
+                probe.issues.push("Unreachable".to_string());
+            }
+            probe.rejected = reject;
+            return;
+        }
+        let supported_nips = probe
+            .document
+            .as_ref()
+            .and_then(|document| document.supported_nips.clone())
+            .unwrap_or_default();
+        for nip in requirements.required_nips.iter() {
+            if !supported_nips.contains(nip) {
+                probe.issues.push(format!("NIP-{:02} not supported", nip));
+                probe.rejected = true;
+            }
+        }
+
+        let restricted = probe.auth_required || probe.restricted_writes;
+        for (applies, action, issue) in [
+            (probe.payment_required, requirements.paid, "Paid relay"),
+            (restricted, requirements.restricted, "Restricted relay"),
+        ] {
+            if !applies || action == FatCrabRelayPolicyAction::Allow {
+                continue;
+            }
+            probe.issues.push(issue.to_string());
+            probe.rejected |= action == FatCrabRelayPolicyAction::Reject;
+        }
+    }
+
+    // Only goes out to the relay when there are requirements to check against
+    pub(crate) async fn check(&self, url: &Url) -> Result<(), FatCrabError> {
+        if self.requirements.lock().unwrap().is_default() {
+            return Ok(());
+        }
+        let probe = self.probe(url).await;
+        if probe.rejected {
+            return Err(FatCrabError::Simple {
+                description: format!(
+                    "Relay {} does not meet requirements - {}",
+                    url,
+                    probe.issues.join(", ")
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    // Issues found by the last probe of the relay
+    pub(crate) fn issues(&self, url: &str) -> Vec<String> {
+        self.probes
+            .lock()
+            .unwrap()
+            .get(url)
+            .map(|probe| probe.issues.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(reachable: bool, supported_nips: Vec<u16>) -> FatCrabRelayProbe {
+        let document = RelayInformationDocument {
+            name: None,
+            description: None,
+            pubkey: None,
+            contact: None,
+            supported_nips: Some(supported_nips),
+            software: None,
+            version: None,
+            relay_countries: Vec::new(),
+            language_tags: Vec::new(),
+            tags: Vec::new(),
+            posting_policy: None,
+            payments_url: None,
+            icon: None,
+        };
+        FatCrabRelayProbe {
+            url: "wss://relay.example/".to_string(),
+            reachable,
+            latency_ms: None,
+            document: reachable.then_some(document),
+            payment_required: false,
+            auth_required: false,
+            restricted_writes: false,
+            issues: Vec::new(),
+            rejected: false,
+        }
+    }
+
+    fn requirements(
+        required_nips: Vec<u16>,
+        paid: FatCrabRelayPolicyAction,
+        restricted: FatCrabRelayPolicyAction,
+    ) -> FatCrabRelayRequirements {
+        FatCrabRelayRequirements {
+            required_nips,
+            paid,
+            restricted,
+        }
+    }
+
+    #[test]
+    fn evaluate_rejects_missing_nips() {
+        let checker = RelayChecker::new();
+        let requirements = requirements(
+            vec![1, 4],
+            FatCrabRelayPolicyAction::Allow,
+            FatCrabRelayPolicyAction::Allow,
+        );
+
+        let mut supported = probe(true, vec![1, 4, 11]);
+        checker.evaluate(&mut supported, &requirements);
+        assert!(!supported.rejected);
+        assert!(supported.issues.is_empty());
+
+        let mut unsupported = probe(true, vec![1, 11]);
+        checker.evaluate(&mut unsupported, &requirements);
+        assert!(unsupported.rejected);
+        assert_eq!(unsupported.issues, vec!["NIP-04 not supported".to_string()]);
+    }
+
+    #[test]
+    fn evaluate_flags_or_rejects_paid_and_restricted_relays() {
+        let checker = RelayChecker::new();
+        let requirements = requirements(
+            Vec::new(),
+            FatCrabRelayPolicyAction::Flag,
+            FatCrabRelayPolicyAction::Reject,
+        );
+
+        let mut paid = probe(true, Vec::new());
+        paid.payment_required = true;
+        checker.evaluate(&mut paid, &requirements);
+        assert!(!paid.rejected);
+        assert_eq!(paid.issues, vec!["Paid relay".to_string()]);
+
+        let mut restricted = probe(true, Vec::new());
+        restricted.auth_required = true;
+        checker.evaluate(&mut restricted, &requirements);
+        assert!(restricted.rejected);
+        assert_eq!(restricted.issues, vec!["Restricted relay".to_string()]);
+    }
+
+    #[test]
+    fn evaluate_allows_anything_by_default() {
+        let checker = RelayChecker::new();
+        let mut paid = probe(true, Vec::new());
+        paid.payment_required = true;
+        paid.restricted_writes = true;
+        checker.evaluate(&mut paid, &FatCrabRelayRequirements::default());
+        assert!(!paid.rejected);
+        assert!(paid.issues.is_empty());
+    }
+
+    #[test]
+    fn evaluate_treats_unreachable_relays_as_failing_every_requirement() {
+        let checker = RelayChecker::new();
+        let mut unreachable = probe(false, Vec::new());
+        checker.evaluate(
+            &mut unreachable,
+            &requirements(
+                Vec::new(),
+                FatCrabRelayPolicyAction::Allow,
+                FatCrabRelayPolicyAction::Allow,
+            ),
+        );
+        assert!(!unreachable.rejected);
+        assert!(unreachable.issues.is_empty());
+
+        let mut unreachable = probe(false, Vec::new());
+        checker.evaluate(
+            &mut unreachable,
+            &requirements(
+                Vec::new(),
+                FatCrabRelayPolicyAction::Flag,
+                FatCrabRelayPolicyAction::Allow,
+            ),
+        );
+        assert!(!unreachable.rejected);
+        assert_eq!(unreachable.issues, vec!["Unreachable".to_string()]);
+
+        for requirements in [
+            requirements(
+                Vec::new(),
+                FatCrabRelayPolicyAction::Reject,
+                FatCrabRelayPolicyAction::Allow,
+            ),
+            requirements(
+                Vec::new(),
+                FatCrabRelayPolicyAction::Flag,
+                FatCrabRelayPolicyAction::Reject,
+            ),
+            requirements(
+                vec![1],
+                FatCrabRelayPolicyAction::Allow,
+                FatCrabRelayPolicyAction::Allow,
+            ),
+        ] {
+            let mut unreachable = probe(false, Vec::new());
+            checker.evaluate(&mut unreachable, &requirements);
+            assert!(unreachable.rejected);
+            assert_eq!(unreachable.issues, vec!["Unreachable".to_string()]);
+        }
+    }
+}
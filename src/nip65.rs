@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use fatcrab_trading::trader::FatCrabTrader as InnerTrader;
+use nostr_sdk::nips::nip65;
+use nostr_sdk::{Client, Filter, Kind, PublicKey, RelayMetadata};
+use tracing::warn;
+use url::Url;
+
+use crate::error::FatCrabError;
+use crate::relay::RELAY_REASON_REMOVED;
+use crate::trader::TraderContext;
+use crate::RUNTIME;
+
+const NIP65_QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) const RELAY_REASON_TRADE: &str = "Added for trade";
+
+pub struct FatCrabPeerRelay {
+    pub pubkey: String,
+    pub url: String,
+    pub read: bool,
+    pub write: bool,
+}
+
+// The Trading Engine does not expose its Nostr client, so relay lists are
+// fetched through a separate client on the same relays
+pub(crate) struct Nip65Client {
+    client: Client,
+    // Relays connected on behalf of a trade, released once the trade ends
+    trade_relays: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl Nip65Client {
+    pub(crate) fn new() -> Self {
+        Self {
+            client: Client::default(),
+            trade_relays: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn connect(&self, relay_urls: &[String]) -> Result<(), FatCrabError> {
+        if relay_urls.is_empty() {
+            return Err(FatCrabError::Simple {
+                description: "No relays to reach peers through".to_string(),
+            });
+        }
+        for relay_url in relay_urls {
+            self.client.add_relay(relay_url.as_str()).await?;
+        }
+        self.client.connect().await;
+        Ok(())
+    }
+
+    // Latest relay list of each pubkey, a pubkey without one is left out
+    pub(crate) async fn fetch(
+        &self,
+        relay_urls: &[String],
+        pubkeys: &[String],
+    ) -> Result<Vec<FatCrabPeerRelay>, FatCrabError> {
+        let authors = pubkeys
+            .iter()
+            .map(|pubkey| PublicKey::from_str(pubkey))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| FatCrabError::Nostr {
+                description: e.to_string(),
+            })?;
+        self.connect(relay_urls).await?;
+
+        let filter = Filter::new().kind(Kind::RelayList).authors(authors);
+        let mut events = self
+            .client
+            .get_events_of(vec![filter], Some(NIP65_QUERY_TIMEOUT))
+            .await?;
+        events.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+
+        let mut peer_relays = Vec::new();
+        let mut seen = Vec::new();
+        for event in events {
+            let pubkey = event.pubkey.to_string();
+            if seen.contains(&pubkey) {
+                continue;
+            }
+            for (url, metadata) in nip65::extract_relay_list(&event) {
+                peer_relays.push(FatCrabPeerRelay {
+                    pubkey: pubkey.clone(),
+                    url: url.to_string(),
+                    read: metadata != Some(RelayMetadata::Write),
+                    write: metadata != Some(RelayMetadata::Read),
+                });
+            }
+            seen.push(pubkey);
+        }
+        Ok(peer_relays)
+    }
+
+    pub(crate) fn track(&self, trade_uuid: &str, relay_urls: Vec<String>) {
+        let mut trade_relays = self.trade_relays.lock().unwrap();
+        let tracked = trade_relays.entry(trade_uuid.to_string()).or_default();
+        for relay_url in relay_urls {
+            if !tracked.contains(&relay_url) {
+                tracked.push(relay_url);
+            }
+        }
+    }
+
+    pub(crate) fn tracked_trades(&self) -> Vec<String> {
+        self.trade_relays.lock().unwrap().keys().cloned().collect()
+    }
+
+    // Relays of the trade that no other ongoing trade still needs
+    pub(crate) fn release(&self, trade_uuid: &str) -> Vec<String> {
+        let mut trade_relays = self.trade_relays.lock().unwrap();
+        let Some(relay_urls) = trade_relays.remove(trade_uuid) else {
+            return Vec::new();
+        };
+        relay_urls
+            .into_iter()
+            .filter(|relay_url| !trade_relays.values().any(|urls| urls.contains(relay_url)))
+            .collect()
+    }
+}
+
+// Peer relays worth connecting to for a trade. We read the peer's events from the
+// relays it writes to, and reach it on the relays it reads from, so both kinds count.
+pub(crate) fn trade_relay_urls(peer_relays: &[FatCrabPeerRelay], current: &[String]) -> Vec<Url> {
+    let mut urls: Vec<Url> = Vec::new();
+    for peer_relay in peer_relays {
+        let Ok(url) = Url::parse(&peer_relay.url) else {
+            continue;
+        };
+        if current.contains(&url.to_string()) || urls.contains(&url) {
+            continue;
+        }
+        urls.push(url);
+    }
+    urls
+}
+
+// Connects to the relays on the peer's relay list for as long as the trade runs.
+// They are not persisted and are removed again once the trade ends. Returns the relays added.
+pub(crate) async fn connect_trade_relays(
+    inner: &InnerTrader,
+    ctx: &TraderContext,
+    trade_uuid: &str,
+    peer_pubkey: String,
+) -> Result<Vec<String>, FatCrabError> {
+    let current: Vec<String> = inner
+        .get_relays()
+        .await
+        .into_iter()
+        .map(|relay| relay.url.to_string())
+        .collect();
+    let peer_relays = ctx.nip65_client.fetch(&current, &[peer_pubkey]).await?;
+    let mut urls = Vec::new();
+    // Held to the same requirements as relays added by hand
+    for url in trade_relay_urls(&peer_relays, &current) {
+        match ctx.relay_checker.check(&url).await {
+            Ok(()) => urls.push(url),
+            Err(error) => warn!("Skipping trade {} relay - {}", trade_uuid, error),
+        }
+    }
+    if urls.is_empty() {
+        return Ok(Vec::new());
+    }
+    let relay_urls: Vec<String> = urls.iter().map(|url| url.to_string()).collect();
+    for relay_url in relay_urls.iter() {
+        ctx.relay_monitor.set_reason(relay_url, RELAY_REASON_TRADE);
+        ctx.relay_monitor.remember(relay_url);
+    }
+    ctx.nip65_client.track(trade_uuid, relay_urls.clone());
+
+    let relays = urls.into_iter().map(|url| (url, None)).collect();
+    inner.add_relays(relays).await?;
+    Ok(relay_urls)
+}
+
+// Done for every trade once its peer is known. A peer without a relay list is reached
+// on our own relays as before, so a failure here only costs a warning.
+pub(crate) fn spawn_connect_trade_relays(
+    ctx: Arc<TraderContext>,
+    trade_uuid: String,
+    peer_pubkey: String,
+) {
+    RUNTIME.spawn(async move {
+        if ctx.trade_history.has_record(&trade_uuid) || ctx.notif_hub.has_ended(&trade_uuid) {
+            return;
+        }
+        let Some(inner) = ctx.inner.upgrade() else {
+            return;
+        };
+        if let Err(error) = connect_trade_relays(&inner, &ctx, &trade_uuid, peer_pubkey).await {
+            warn!(
+                "Failed to connect to the peer's relays for trade {} - {}",
+                trade_uuid, error
+            );
+        }
+    });
+}
+
+// Removes the relays connected for a trade, unless the app has since added them itself
+pub(crate) async fn release_trade_relays(
+    inner: &InnerTrader,
+    ctx: &TraderContext,
+    trade_uuid: &str,
+) {
+    let persisted: Vec<String> = ctx
+        .relay_store
+        .relays()
+        .into_iter()
+        .map(|relay_addr| relay_addr.url)
+        .collect();
+
+    for relay_url in ctx.nip65_client.release(trade_uuid) {
+        if persisted.contains(&relay_url) {
+            continue;
+        }
+        let Ok(url) = Url::parse(&relay_url) else {
+            continue;
+        };
+        ctx.relay_monitor
+            .set_reason(&relay_url, RELAY_REASON_REMOVED);
+        ctx.relay_monitor.forget(&relay_url);
+        if let Err(error) = inner.remove_relay(url).await {
+            warn!(
+                "Failed to remove relay {} after trade {} - {}",
+                relay_url,
+                trade_uuid,
+                FatCrabError::from(error)
+            );
+        }
+    }
+}
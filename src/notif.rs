@@ -0,0 +1,871 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::mem::{discriminant, take};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::error::FatCrabError;
+use crate::event::{EventQueue, FatCrabEvent};
+use crate::logging::trade_span;
+use crate::maker::{FatCrabMakerNotifOfferStruct, FatCrabMakerNotifPeerStruct};
+use crate::offer::FatCrabOfferEnvelope;
+use crate::peer::FatCrabPeerEnvelope;
+use crate::persist::{ffi_data_dir, load_json, save_json};
+use crate::taker::{FatCrabTakerNotifPeerStruct, FatCrabTakerNotifTradeRspStruct};
+use crate::types::{
+    FatCrabMakerNotifDelegate, FatCrabNotifOverflowDelegate, FatCrabTakerNotifDelegate,
+    FatCrabTraderNotifDelegate,
+};
+
+const NOTIF_ACKS_FILENAME: &str = "notif_acks.json";
+const NOTIF_DISPATCH_THREADS: usize = 4;
+
+#[derive(Clone)]
+pub(crate) enum TradeNotif {
+    MakerOffer(FatCrabMakerNotifOfferStruct),
+    MakerPeer(FatCrabMakerNotifPeerStruct),
+    TakerTradeRsp(FatCrabTakerNotifTradeRspStruct),
+    TakerPeer(FatCrabTakerNotifPeerStruct),
+}
+
+impl TradeNotif {
+    pub(crate) fn seq(&self) -> u64 {
+        match self {
+            TradeNotif::MakerOffer(notif) => notif.seq,
+            TradeNotif::MakerPeer(notif) => notif.seq,
+            TradeNotif::TakerTradeRsp(notif) => notif.seq,
+            TradeNotif::TakerPeer(notif) => notif.seq,
+        }
+    }
+
+    // Repeats of the same content, as replayed after a reconnect, share the key
+    fn key(&self) -> String {
+        match self {
+            TradeNotif::MakerOffer(notif) => offer_notif_key(&notif.offer_envelope),
+            TradeNotif::MakerPeer(notif) => peer_notif_key(&notif.peer_envelope),
+            TradeNotif::TakerTradeRsp(_) => trade_rsp_notif_key(),
+            TradeNotif::TakerPeer(notif) => peer_notif_key(&notif.peer_envelope),
+        }
+    }
+
+    fn deliver(self, delegate: &NotifDelegate) {
+        match (self, delegate) {
+            (TradeNotif::MakerOffer(notif), NotifDelegate::Maker(delegate)) => {
+                delegate.on_maker_offer_notif(notif)
+            }
+            (TradeNotif::MakerPeer(notif), NotifDelegate::Maker(delegate)) => {
+                delegate.on_maker_peer_notif(notif)
+            }
+            (TradeNotif::TakerTradeRsp(notif), NotifDelegate::Taker(delegate)) => {
+                delegate.on_taker_trade_rsp_notif(notif)
+            }
+            (TradeNotif::TakerPeer(notif), NotifDelegate::Taker(delegate)) => {
+                delegate.on_taker_peer_notif(notif)
+            }
+            _ => warn!("Notification dropped - delegate registered for the other role"),
+        }
+    }
+}
+
+// What the overflow policies need to know of a queued notification
+trait Queued {
+    fn seq(&self) -> u64;
+    // Whether one supersedes the other, being of the same kind and carrying the same content
+    fn same_identity(&self, other: &Self) -> bool;
+}
+
+impl Queued for TradeNotif {
+    fn seq(&self) -> u64 {
+        TradeNotif::seq(self)
+    }
+
+    fn same_identity(&self, other: &TradeNotif) -> bool {
+        discriminant(self) == discriminant(other) && self.key() == other.key()
+    }
+}
+
+// Identifies the content of a notification so it can be recognized again after a restart,
+// when the Trading Engine is queried for what it received while the app was not running
+pub(crate) fn offer_notif_key(offer_envelope: &FatCrabOfferEnvelope) -> String {
+    format!("offer:{}", offer_envelope.event_id())
+}
+
+pub(crate) fn peer_notif_key(peer_envelope: &FatCrabPeerEnvelope) -> String {
+    format!("peer:{}", peer_envelope.message().txid)
+}
+
+pub(crate) fn trade_rsp_notif_key() -> String {
+    "trade_rsp".to_string()
+}
+
+#[derive(Clone)]
+pub(crate) enum NotifDelegate {
+    Maker(Arc<dyn FatCrabMakerNotifDelegate>),
+    Taker(Arc<dyn FatCrabTakerNotifDelegate>),
+}
+
+impl NotifDelegate {
+    fn on_overflow(&self, overflow: FatCrabNotifOverflowStruct) {
+        match self {
+            NotifDelegate::Maker(delegate) => delegate.on_maker_notif_overflow(overflow),
+            NotifDelegate::Taker(delegate) => delegate.on_taker_notif_overflow(overflow),
+        }
+    }
+}
+
+type SharedOverflowDelegate = Arc<RwLock<Option<Arc<dyn FatCrabNotifOverflowDelegate>>>>;
+
+#[derive(Default, Serialize, Deserialize)]
+struct TradeNotifAcks {
+    acked_keys: HashSet<String>,
+    pending_seqs: HashMap<String, u64>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct NotifAckData {
+    next_seq: u64,
+    trades: HashMap<String, TradeNotifAcks>,
+}
+
+const DEFAULT_NOTIF_BUFFER_SIZE: u32 = 64;
+
+pub enum FatCrabNotifOverflowPolicy {
+    Block,
+    DropOldest,
+    Coalesce,
+}
+
+pub struct FatCrabNotifBufferConfig {
+    pub size: u32,
+    pub policy: FatCrabNotifOverflowPolicy,
+}
+
+impl Default for FatCrabNotifBufferConfig {
+    fn default() -> Self {
+        Self {
+            size: DEFAULT_NOTIF_BUFFER_SIZE,
+            policy: FatCrabNotifOverflowPolicy::Block,
+        }
+    }
+}
+
+// Dropped notifications stay unacknowledged, registering again replays them
+#[derive(Clone)]
+pub struct FatCrabNotifOverflowStruct {
+    pub trade_uuid: String,
+    pub dropped_count: u64,
+    pub first_dropped_seq: u64,
+    pub last_dropped_seq: u64,
+}
+
+enum Delivery {
+    Notif(TradeNotif),
+    Overflow(FatCrabNotifOverflowStruct),
+}
+
+enum Push {
+    Queued,
+    // Handed back under the Block policy, to be pushed again once there is room
+    Full(TradeNotif),
+    Inactive,
+}
+
+struct BufferState<N = TradeNotif> {
+    active: bool,
+    // Whether a delivery job for the buffer is waiting or running on its dispatcher
+    scheduled: bool,
+    queue: VecDeque<N>,
+    overflow: Option<FatCrabNotifOverflowStruct>,
+}
+
+impl<N: Queued> BufferState<N> {
+    fn new() -> Self {
+        Self {
+            active: true,
+            scheduled: false,
+            queue: VecDeque::new(),
+            overflow: None,
+        }
+    }
+
+    // Makes room for the notification once the queue holds size of them. Under the Block
+    // policy a full queue is left alone, false when the notification is to be handed back.
+    fn make_room(
+        &mut self,
+        trade_uuid: &str,
+        size: usize,
+        policy: &FatCrabNotifOverflowPolicy,
+        notif: &N,
+        retry: bool,
+    ) -> bool {
+        if self.queue.len() < size {
+            return true;
+        }
+        let dropped = match policy {
+            FatCrabNotifOverflowPolicy::Block if retry => return false,
+            FatCrabNotifOverflowPolicy::Block => None,
+            FatCrabNotifOverflowPolicy::DropOldest => self.queue.pop_front(),
+            FatCrabNotifOverflowPolicy::Coalesce => {
+                coalesce_index(&self.queue, notif).and_then(|index| self.queue.remove(index))
+            }
+        };
+        if let Some(dropped) = dropped {
+            self.record_drop(trade_uuid, dropped.seq());
+        }
+        true
+    }
+
+    fn record_drop(&mut self, trade_uuid: &str, seq: u64) {
+        let overflow = self.overflow.get_or_insert(FatCrabNotifOverflowStruct {
+            trade_uuid: trade_uuid.to_string(),
+            dropped_count: 0,
+            first_dropped_seq: seq,
+            last_dropped_seq: seq,
+        });
+        overflow.dropped_count += 1;
+        overflow.first_dropped_seq = overflow.first_dropped_seq.min(seq);
+        overflow.last_dropped_seq = overflow.last_dropped_seq.max(seq);
+    }
+}
+
+// Notifications waiting for one registered delegate, bounded as the app configured.
+// Delivered by the dispatcher thread the trade is assigned to, one at a time.
+struct DelegateBuffer {
+    trade_uuid: String,
+    delegate: NotifDelegate,
+    overflow_delegate: SharedOverflowDelegate,
+    size: usize,
+    policy: FatCrabNotifOverflowPolicy,
+    state: Mutex<BufferState>,
+    dispatch_tx: mpsc::Sender<DispatchJob>,
+    // Wakes publishers waiting for room under the Block policy
+    space: Notify,
+}
+
+impl DelegateBuffer {
+    fn new(
+        trade_uuid: &str,
+        delegate: NotifDelegate,
+        overflow_delegate: SharedOverflowDelegate,
+        config: FatCrabNotifBufferConfig,
+        dispatch_tx: mpsc::Sender<DispatchJob>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            trade_uuid: trade_uuid.to_string(),
+            delegate,
+            overflow_delegate,
+            size: config.size.max(1) as usize,
+            policy: config.policy,
+            state: Mutex::new(BufferState::new()),
+            dispatch_tx,
+            space: Notify::new(),
+        })
+    }
+
+    // Anything still queued for an unregistered delegate is discarded.
+    // A delivery in progress, if any, still completes.
+    fn deactivate(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.active = false;
+        state.queue.clear();
+        state.overflow = None;
+        self.space.notify_waiters();
+    }
+
+    // Never waits. Under the Block policy a full buffer hands the notification back when
+    // asked to, otherwise it exceeds the size, as replays do since nothing would retry them.
+    fn push(self: &Arc<Self>, notif: TradeNotif, retry: bool) -> Push {
+        let mut state = self.state.lock().unwrap();
+        if !state.active {
+            return Push::Inactive;
+        }
+
+        if !state.make_room(&self.trade_uuid, self.size, &self.policy, &notif, retry) {
+            return Push::Full(notif);
+        }
+        state.queue.push_back(notif);
+        if !state.scheduled {
+            state.scheduled = true;
+            _ = self.dispatch_tx.send(DispatchJob::Deliver(self.clone()));
+        }
+        Push::Queued
+    }
+
+    // One delivery per job, an overflow ahead of whatever survived it. While anything is
+    // left the buffer goes back in line, behind the other trades sharing its dispatcher.
+    fn deliver_next(self: &Arc<Self>) {
+        let delivery = {
+            let mut state = self.state.lock().unwrap();
+            let delivery = match state.overflow.take() {
+                Some(overflow) => Some(Delivery::Overflow(overflow)),
+                None => state.queue.pop_front().map(Delivery::Notif),
+            };
+            match delivery {
+                Some(delivery) if state.active => delivery,
+                _ => {
+                    state.scheduled = false;
+                    return;
+                }
+            }
+        };
+        self.space.notify_waiters();
+
+        let _span = trade_span(&self.trade_uuid).entered();
+        match delivery {
+            Delivery::Notif(notif) => notif.deliver(&self.delegate),
+            Delivery::Overflow(overflow) => {
+                let overflow_delegate = self.overflow_delegate.read().unwrap().clone();
+                if let Some(overflow_delegate) = overflow_delegate {
+                    overflow_delegate.on_notif_overflow(overflow.clone());
+                }
+                self.delegate.on_overflow(overflow);
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.active && (state.overflow.is_some() || !state.queue.is_empty()) {
+            _ = self.dispatch_tx.send(DispatchJob::Deliver(self.clone()));
+        } else {
+            state.scheduled = false;
+        }
+    }
+}
+
+// Only superseded notifications make room: the newest queued repeat of the incoming one, or
+// else any that a newer repeat follows. Notifications carrying different content, such as
+// offers from different peers, are never merged, and with no repeats the size is exceeded.
+fn coalesce_index<N: Queued>(queue: &VecDeque<N>, notif: &N) -> Option<usize> {
+    if let Some(index) = queue.iter().rposition(|queued| queued.same_identity(notif)) {
+        return Some(index);
+    }
+    queue.iter().enumerate().position(|(index, queued)| {
+        queue
+            .iter()
+            .skip(index + 1)
+            .any(|newer| newer.same_identity(queued))
+    })
+}
+
+enum DispatchJob {
+    TraderEvent(Arc<dyn FatCrabTraderNotifDelegate>, FatCrabEvent),
+    Deliver(Arc<DelegateBuffer>),
+    Call(Box<dyn FnOnce() + Send>),
+    Stop,
+}
+
+#[derive(Default)]
+struct HubTrade {
+    backlog: Vec<(String, TradeNotif)>,
+    registration: Option<Arc<DelegateBuffer>>,
+}
+
+#[derive(Default)]
+struct HubState {
+    acks: NotifAckData,
+    trades: HashMap<String, HubTrade>,
+    attached: HashSet<String>,
+    // Trades that ended, nothing more is captured for them
+    ended: HashSet<String>,
+    // Trades found on startup, announced again to late listeners
+    restored: Vec<FatCrabEvent>,
+    trader_delegate: Option<Arc<dyn FatCrabTraderNotifDelegate>>,
+    overflow_delegate: SharedOverflowDelegate,
+}
+
+// A small fixed set of threads calls every delegate. Trader wide delegates are called from
+// the first one, in order. A trade always goes to the same thread, which keeps its
+// notifications in order, so a slow delegate only holds back the trades sharing its thread.
+struct Dispatchers {
+    senders: Vec<mpsc::Sender<DispatchJob>>,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Dispatchers {
+    fn start() -> Self {
+        let mut senders = Vec::new();
+        let mut threads = Vec::new();
+        for index in 0..NOTIF_DISPATCH_THREADS {
+            let (dispatch_tx, dispatch_rx) = mpsc::channel();
+            let spawned = thread::Builder::new()
+                .name(format!("fatcrab-notif-dispatch-{}", index))
+                .spawn(move || run_dispatcher(dispatch_rx));
+            match spawned {
+                Ok(handle) => threads.push(handle),
+                Err(error) => warn!("Failed to start notification dispatcher - {}", error),
+            }
+            senders.push(dispatch_tx);
+        }
+        Self {
+            senders,
+            threads: Mutex::new(threads),
+        }
+    }
+
+    fn trader(&self) -> &mpsc::Sender<DispatchJob> {
+        &self.senders[0]
+    }
+
+    fn for_trade(&self, trade_uuid: &str) -> mpsc::Sender<DispatchJob> {
+        let mut hasher = DefaultHasher::new();
+        trade_uuid.hash(&mut hasher);
+        let index = hasher.finish() as usize % self.senders.len();
+        self.senders[index].clone()
+    }
+
+    // Each thread stops once the job it is running, if any, finishes
+    fn stop(&self) {
+        for sender in self.senders.iter() {
+            _ = sender.send(DispatchJob::Stop);
+        }
+        let threads = take(&mut *self.threads.lock().unwrap());
+        for handle in threads {
+            // A delegate shutting the Trader down from its own callback cannot wait on itself
+            if handle.thread().id() != thread::current().id() {
+                _ = handle.join();
+            }
+        }
+    }
+}
+
+// Notifications are captured for every trade the Trader knows of, whether a delegate is
+// registered or not. Each gets a sequence number and is held until the app acknowledges it.
+pub(crate) struct NotifHub {
+    path: PathBuf,
+    state: Mutex<HubState>,
+    events: Arc<EventQueue>,
+    dispatchers: Dispatchers,
+}
+
+fn run_dispatcher(dispatch_rx: mpsc::Receiver<DispatchJob>) {
+    while let Ok(job) = dispatch_rx.recv() {
+        match job {
+            DispatchJob::TraderEvent(delegate, event) => delegate.on_trader_event(event),
+            DispatchJob::Deliver(buffer) => buffer.deliver_next(),
+            DispatchJob::Call(call) => call(),
+            DispatchJob::Stop => break,
+        }
+    }
+}
+
+impl NotifHub {
+    pub(crate) fn new(app_dir_path: &str, events: Arc<EventQueue>) -> Self {
+        let path = ffi_data_dir(app_dir_path).join(NOTIF_ACKS_FILENAME);
+        let state = HubState {
+            acks: load_json(&path),
+            ..Default::default()
+        };
+        Self {
+            path,
+            state: Mutex::new(state),
+            events,
+            dispatchers: Dispatchers::start(),
+        }
+    }
+
+    // For other Trader wide delegates, so they are called from the same thread
+    pub(crate) fn run_on_dispatcher(&self, call: impl FnOnce() + Send + 'static) {
+        let job = DispatchJob::Call(Box::new(call));
+        _ = self.dispatchers.trader().send(job);
+    }
+
+    fn dispatch_event(&self, state: &HubState, event: FatCrabEvent) {
+        if let Some(delegate) = &state.trader_delegate {
+            let job = DispatchJob::TraderEvent(delegate.clone(), event);
+            _ = self.dispatchers.trader().send(job);
+        }
+    }
+
+    // Trader wide events, to the event queue and the Trader delegate alike
+    pub(crate) fn emit(&self, event: FatCrabEvent) {
+        let state = self.state.lock().unwrap();
+        self.emit_locked(&state, event);
+    }
+
+    fn emit_locked(&self, state: &HubState, event: FatCrabEvent) {
+        self.events.push(event.clone());
+        self.dispatch_event(state, event);
+    }
+
+    pub(crate) fn restored(&self, event: FatCrabEvent) {
+        let mut state = self.state.lock().unwrap();
+        state.restored.push(event.clone());
+        self.emit_locked(&state, event);
+    }
+
+    // Returns true only for the first caller, who is then responsible for hooking up the trade
+    pub(crate) fn begin_attach(&self, trade_uuid: &str) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .attached
+            .insert(trade_uuid.to_string())
+    }
+
+    pub(crate) fn abort_attach(&self, trade_uuid: &str) {
+        self.state.lock().unwrap().attached.remove(trade_uuid);
+    }
+
+    // Under the Block policy this waits for the delegate to make room in its buffer,
+    // which in turn holds back the Trading Engine once its own channel fills up
+    pub(crate) async fn publish(
+        &self,
+        trade_uuid: &str,
+        key: String,
+        make_notif: impl FnOnce(u64) -> TradeNotif,
+    ) {
+        // The hub is never locked while waiting, the delegate may call back into it
+        let Some((buffer, mut notif)) = self.capture(trade_uuid, key, make_notif) else {
+            return;
+        };
+        loop {
+            let space = buffer.space.notified();
+            match buffer.push(notif, true) {
+                Push::Full(returned) => {
+                    notif = returned;
+                    space.await;
+                }
+                Push::Queued | Push::Inactive => return,
+            }
+        }
+    }
+
+    // For notifications recovered from the Trading Engine, never waits
+    pub(crate) fn replay(
+        &self,
+        trade_uuid: &str,
+        key: String,
+        make_notif: impl FnOnce(u64) -> TradeNotif,
+    ) {
+        if let Some((buffer, notif)) = self.capture(trade_uuid, key, make_notif) {
+            buffer.push(notif, false);
+        }
+    }
+
+    fn capture(
+        &self,
+        trade_uuid: &str,
+        key: String,
+        make_notif: impl FnOnce(u64) -> TradeNotif,
+    ) -> Option<(Arc<DelegateBuffer>, TradeNotif)> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        if state.ended.contains(trade_uuid) {
+            return None;
+        }
+
+        let acks = state.acks.trades.entry(trade_uuid.to_string()).or_default();
+        if acks.acked_keys.contains(&key) {
+            return None;
+        }
+        let trade = state.trades.entry(trade_uuid.to_string()).or_default();
+        if trade
+            .backlog
+            .iter()
+            .any(|(backlog_key, _)| *backlog_key == key)
+        {
+            return None;
+        }
+
+        let seq = match acks.pending_seqs.get(&key) {
+            Some(seq) => *seq,
+            None => {
+                state.acks.next_seq += 1;
+                let seq = state.acks.next_seq;
+                acks.pending_seqs.insert(key.clone(), seq);
+                seq
+            }
+        };
+
+        let notif = make_notif(seq);
+        trade.backlog.push((key, notif.clone()));
+        trade.backlog.sort_by_key(|(_, notif)| notif.seq());
+        let event = FatCrabEvent::from_notif(trade_uuid, notif.clone());
+        let delivery = trade
+            .registration
+            .as_ref()
+            .map(|buffer| (buffer.clone(), notif));
+        self.emit_locked(state, event);
+        self.save(&state.acks);
+        delivery
+    }
+
+    // Replays every unacknowledged notification of the trade before any new ones
+    pub(crate) fn register(
+        &self,
+        trade_uuid: &str,
+        delegate: NotifDelegate,
+        config: FatCrabNotifBufferConfig,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let overflow_delegate = state.overflow_delegate.clone();
+        let trade = state.trades.entry(trade_uuid.to_string()).or_default();
+
+        if let Some(buffer) = trade.registration.take() {
+            buffer.deactivate();
+        }
+        let dispatch_tx = self.dispatchers.for_trade(trade_uuid);
+        let buffer =
+            DelegateBuffer::new(trade_uuid, delegate, overflow_delegate, config, dispatch_tx);
+        for (_, notif) in trade.backlog.iter() {
+            buffer.push(notif.clone(), false);
+        }
+        trade.registration = Some(buffer);
+    }
+
+    // Restored trades, then every unacknowledged notification across all trades
+    fn replay_events(state: &HubState) -> Vec<FatCrabEvent> {
+        let mut backlog: Vec<(&String, &TradeNotif)> = state
+            .trades
+            .iter()
+            .flat_map(|(trade_uuid, trade)| {
+                trade
+                    .backlog
+                    .iter()
+                    .map(move |(_, notif)| (trade_uuid, notif))
+            })
+            .collect();
+        backlog.sort_by_key(|(_, notif)| notif.seq());
+
+        let mut events = state.restored.clone();
+        for (trade_uuid, notif) in backlog {
+            events.push(FatCrabEvent::from_notif(trade_uuid, notif.clone()));
+        }
+        events
+    }
+
+    // Starts queueing events for polling, seeded with the replayed events
+    pub(crate) fn enable_events(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        if !self.events.enable() {
+            return false;
+        }
+        for event in Self::replay_events(&state) {
+            self.events.push(event);
+        }
+        true
+    }
+
+    // Not bounded by a buffer config, the Trader delegate sees everything in order
+    pub(crate) fn register_trader_delegate(&self, delegate: Arc<dyn FatCrabTraderNotifDelegate>) {
+        let mut state = self.state.lock().unwrap();
+        state.trader_delegate = Some(delegate);
+        for event in Self::replay_events(&state) {
+            self.dispatch_event(&state, event);
+        }
+    }
+
+    pub(crate) fn unregister_trader_delegate(&self) {
+        self.state.lock().unwrap().trader_delegate = None;
+    }
+
+    pub(crate) fn register_overflow_delegate(
+        &self,
+        delegate: Arc<dyn FatCrabNotifOverflowDelegate>,
+    ) {
+        let state = self.state.lock().unwrap();
+        *state.overflow_delegate.write().unwrap() = Some(delegate);
+    }
+
+    pub(crate) fn unregister_overflow_delegate(&self) {
+        let state = self.state.lock().unwrap();
+        *state.overflow_delegate.write().unwrap() = None;
+    }
+
+    pub(crate) fn unregister(&self, trade_uuid: &str) {
+        if let Some(trade) = self.state.lock().unwrap().trades.get_mut(trade_uuid) {
+            if let Some(buffer) = trade.registration.take() {
+                buffer.deactivate();
+            }
+        }
+    }
+
+    // Drops every registration and stops the dispatchers once their current delivery finishes
+    pub(crate) fn shutdown(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.trader_delegate = None;
+        for trade in state.trades.values_mut() {
+            if let Some(buffer) = trade.registration.take() {
+                buffer.deactivate();
+            }
+        }
+        drop(state);
+        self.dispatchers.stop();
+    }
+
+    // Acknowledges every notification of the trade up to and including the sequence number
+    pub(crate) fn acknowledge(&self, trade_uuid: &str, seq: u64) -> Result<(), FatCrabError> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+
+        let acks = state.acks.trades.entry(trade_uuid.to_string()).or_default();
+        if let Some(trade) = state.trades.get_mut(trade_uuid) {
+            trade.backlog.retain(|(key, notif)| {
+                if notif.seq() > seq {
+                    return true;
+                }
+                acks.pending_seqs.remove(key);
+                acks.acked_keys.insert(key.clone());
+                false
+            });
+        }
+        save_json(&self.path, &state.acks)
+    }
+
+    pub(crate) fn has_ended(&self, trade_uuid: &str) -> bool {
+        self.state.lock().unwrap().ended.contains(trade_uuid)
+    }
+
+    // Whatever is left unacknowledged of an ended trade is moot, and its keys are no longer
+    // needed to recognize replays, as ended trades are not replayed
+    pub(crate) fn trade_ended(&self, trade_uuid: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.ended.insert(trade_uuid.to_string());
+        if let Some(trade) = state.trades.get_mut(trade_uuid) {
+            trade.backlog.clear();
+        }
+        if state.acks.trades.remove(trade_uuid).is_some() {
+            self.save(&state.acks);
+        }
+    }
+
+    fn save(&self, acks: &NotifAckData) {
+        if let Err(error) = save_json(&self.path, acks) {
+            warn!(
+                "Failed to persist notification acknowledgements - {}",
+                error
+            );
+        }
+    }
+}
+
+impl Drop for NotifHub {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct TestNotif {
+        seq: u64,
+        key: char,
+    }
+
+    impl Queued for TestNotif {
+        fn seq(&self) -> u64 {
+            self.seq
+        }
+
+        fn same_identity(&self, other: &TestNotif) -> bool {
+            self.key == other.key
+        }
+    }
+
+    const TRADE_UUID: &str = "trade";
+
+    fn queue(keys: &str) -> VecDeque<TestNotif> {
+        keys.chars()
+            .enumerate()
+            .map(|(index, key)| TestNotif {
+                seq: index as u64 + 1,
+                key,
+            })
+            .collect()
+    }
+
+    fn keys(queue: &VecDeque<TestNotif>) -> String {
+        queue.iter().map(|notif| notif.key).collect()
+    }
+
+    fn full_state(keys: &str) -> BufferState<TestNotif> {
+        let mut state = BufferState::new();
+        state.queue = queue(keys);
+        state
+    }
+
+    fn incoming(key: char) -> TestNotif {
+        TestNotif { seq: 100, key }
+    }
+
+    #[test]
+    fn coalesce_index_prefers_the_newest_repeat() {
+        assert_eq!(coalesce_index(&queue("abab"), &incoming('a')), Some(2));
+        assert_eq!(coalesce_index(&queue("abab"), &incoming('b')), Some(3));
+    }
+
+    #[test]
+    fn coalesce_index_falls_back_to_a_superseded_notification() {
+        assert_eq!(coalesce_index(&queue("abcb"), &incoming('d')), Some(1));
+        assert_eq!(coalesce_index(&queue("abc"), &incoming('d')), None);
+        assert_eq!(coalesce_index(&queue(""), &incoming('d')), None);
+    }
+
+    #[test]
+    fn make_room_leaves_room_below_the_size() {
+        let policy = FatCrabNotifOverflowPolicy::Block;
+        let mut state = full_state("ab");
+        assert!(state.make_room(TRADE_UUID, 3, &policy, &incoming('a'), true));
+        assert_eq!(keys(&state.queue), "ab");
+        assert!(state.overflow.is_none());
+    }
+
+    #[test]
+    fn make_room_blocks_or_exceeds_the_size() {
+        let policy = FatCrabNotifOverflowPolicy::Block;
+        let mut state = full_state("ab");
+        assert!(!state.make_room(TRADE_UUID, 2, &policy, &incoming('a'), true));
+        assert!(state.make_room(TRADE_UUID, 2, &policy, &incoming('a'), false));
+        assert_eq!(keys(&state.queue), "ab");
+        assert!(state.overflow.is_none());
+    }
+
+    #[test]
+    fn make_room_drops_the_oldest() {
+        let policy = FatCrabNotifOverflowPolicy::DropOldest;
+        let mut state = full_state("abc");
+        assert!(state.make_room(TRADE_UUID, 3, &policy, &incoming('d'), true));
+        assert!(state.make_room(TRADE_UUID, 2, &policy, &incoming('d'), true));
+        assert_eq!(keys(&state.queue), "c");
+
+        let overflow = state.overflow.unwrap();
+        assert_eq!(overflow.trade_uuid, TRADE_UUID);
+        assert_eq!(overflow.dropped_count, 2);
+        assert_eq!(overflow.first_dropped_seq, 1);
+        assert_eq!(overflow.last_dropped_seq, 2);
+    }
+
+    #[test]
+    fn make_room_coalesces_superseded_notifications_only() {
+        let policy = FatCrabNotifOverflowPolicy::Coalesce;
+        let mut state = full_state("abca");
+        assert!(state.make_room(TRADE_UUID, 4, &policy, &incoming('b'), true));
+        assert_eq!(keys(&state.queue), "aca");
+        assert_eq!(state.overflow.as_ref().unwrap().first_dropped_seq, 2);
+
+        let mut state = full_state("abc");
+        assert!(state.make_room(TRADE_UUID, 3, &policy, &incoming('d'), true));
+        assert_eq!(keys(&state.queue), "abc");
+        assert!(state.overflow.is_none());
+    }
+
+    #[test]
+    fn record_drop_spans_every_dropped_seq() {
+        let mut state = BufferState::<TestNotif>::new();
+        state.record_drop(TRADE_UUID, 7);
+        state.record_drop(TRADE_UUID, 3);
+        state.record_drop(TRADE_UUID, 5);
+        let overflow = state.overflow.unwrap();
+        assert_eq!(overflow.dropped_count, 3);
+        assert_eq!(overflow.first_dropped_seq, 3);
+        assert_eq!(overflow.last_dropped_seq, 7);
+    }
+}
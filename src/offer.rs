@@ -21,4 +21,9 @@ impl FatCrabOfferEnvelope {
     pub fn pubkey(&self) -> String {
         self.inner.pubkey.to_owned()
     }
+
+    // Nostr event the offer came in, a peer can make more than one offer
+    pub(crate) fn event_id(&self) -> String {
+        self.inner.envelope.event_id.to_string()
+    }
 }
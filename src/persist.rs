@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::warn;
+
+use crate::error::FatCrabError;
+
+const FFI_DATA_DIR: &str = "ffi";
+
+// Data owned by the FFI layer is kept apart from what the Trading Engine persists
+pub(crate) fn ffi_data_dir(app_dir_path: &str) -> PathBuf {
+    Path::new(app_dir_path).join(FFI_DATA_DIR)
+}
+
+pub(crate) fn now_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+pub(crate) fn load_json<T: DeserializeOwned + Default>(path: &Path) -> T {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(_) => return T::default(),
+    };
+    match serde_json::from_slice(&data) {
+        Ok(value) => value,
+        Err(error) => {
+            warn!("Discarding unreadable {} - {}", path.display(), error);
+            T::default()
+        }
+    }
+}
+
+// Write to a temporary file first so a crash never leaves a truncated file behind
+pub(crate) fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<(), FatCrabError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let data = serde_json::to_vec_pretty(value)?;
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
@@ -0,0 +1,477 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use fatcrab_trading::trader::FatCrabTrader as InnerTrader;
+use tracing::warn;
+use url::Url;
+use uuid::Uuid;
+
+use crate::error::FatCrabError;
+use crate::event::FatCrabEvent;
+use crate::nip65::release_trade_relays;
+use crate::persist::{ffi_data_dir, load_json, now_timestamp, save_json};
+use crate::trader::TraderContext;
+use crate::types::{FatCrabRelayNotifDelegate, RelayAddr, RelayHealth, RelayStatus};
+use crate::RUNTIME;
+
+const RELAY_STATUS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const RELAY_LIST_FILENAME: &str = "relays.json";
+
+pub(crate) const RELAY_REASON_ADDED: &str = "Added";
+pub(crate) const RELAY_REASON_REMOVED: &str = "Removed";
+pub(crate) const RELAY_REASON_RECONNECT: &str = "Reconnect requested";
+pub(crate) const RELAY_REASON_AUTO_RECONNECT: &str = "Automatic reconnect";
+pub(crate) const RELAY_REASON_REQUIREMENTS: &str = "Falls short of the relay requirements";
+
+// The relay pool reconnects each relay on its own. The backoff only decides when the FFI
+// steps in for a relay still disconnected. The Trading Engine offers no per-relay
+// reconnect, so stepping in reconnects every relay, connected ones included. A relay it does
+// not bring back waits out a longer delay before the next attempt.
+pub struct FatCrabRelayReconnectPolicy {
+    pub enabled: bool,
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub jitter: f64, // fraction of the delay, 0.2 spreads attempts over +/- 20%
+}
+
+impl Default for FatCrabRelayReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            initial_delay_ms: 5_000,
+            max_delay_ms: 300_000,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl FatCrabRelayReconnectPolicy {
+    // Doubles with every attempt up to the maximum, then spread by the jitter
+    fn delay(&self, attempt: u32) -> Duration {
+        let delay_ms = self
+            .initial_delay_ms
+            .saturating_mul(1u64 << attempt.min(32))
+            .min(self.max_delay_ms) as f64;
+        let random = Uuid::new_v4().as_u128() as f64 / u128::MAX as f64;
+        let jitter = self.jitter.clamp(0.0, 1.0) * (random * 2.0 - 1.0);
+        Duration::from_millis((delay_ms * (1.0 + jitter)) as u64)
+    }
+}
+
+struct Backoff {
+    attempt: u32,
+    next_attempt_at: Instant,
+}
+
+#[derive(Clone)]
+pub struct FatCrabRelayStatusNotifStruct {
+    pub url: String,
+    pub previous_status: Option<RelayStatus>,
+    pub status: RelayStatus,
+    // The Trading Engine does not say why a relay changed status,
+    // only transitions the FFI caused itself come with a reason
+    pub reason: Option<String>,
+    pub timestamp: u64,
+}
+
+#[derive(Default)]
+struct RelayMonitorState {
+    statuses: HashMap<String, RelayStatus>,
+    reasons: HashMap<String, String>,
+    delegate: Option<Arc<dyn FatCrabRelayNotifDelegate>>,
+    health: HashMap<String, RelayHealth>,
+    removed: HashSet<String>,
+    backoffs: HashMap<String, Backoff>,
+    policy: FatCrabRelayReconnectPolicy,
+}
+
+// The relays the app added, so they can be added again on the next launch
+pub(crate) struct RelayStore {
+    path: PathBuf,
+    relays: Mutex<Vec<RelayAddr>>,
+}
+
+impl RelayStore {
+    pub(crate) fn new(app_dir_path: &str) -> Self {
+        let path = ffi_data_dir(app_dir_path).join(RELAY_LIST_FILENAME);
+        let relays = load_json(&path);
+        Self {
+            path,
+            relays: Mutex::new(relays),
+        }
+    }
+
+    pub(crate) fn relays(&self) -> Vec<RelayAddr> {
+        self.relays.lock().unwrap().clone()
+    }
+
+    // Relays are stored by their parsed URL, adding one again replaces it
+    pub(crate) fn upsert(&self, relay_addrs: &[RelayAddr]) -> Result<(), FatCrabError> {
+        let mut relays = self.relays.lock().unwrap();
+        for relay_addr in relay_addrs {
+            let mut relay_addr = relay_addr.clone();
+            relay_addr.url = Url::parse(&relay_addr.url)?.to_string();
+            relays.retain(|relay| relay.url != relay_addr.url);
+            relays.push(relay_addr);
+        }
+        save_json(&self.path, &*relays)
+    }
+
+    pub(crate) fn remove(&self, url: &str) -> Result<(), FatCrabError> {
+        let mut relays = self.relays.lock().unwrap();
+        relays.retain(|relay| relay.url != url);
+        save_json(&self.path, &*relays)
+    }
+
+    pub(crate) fn clear(&self) -> Result<(), FatCrabError> {
+        let mut relays = self.relays.lock().unwrap();
+        relays.clear();
+        save_json(&self.path, &*relays)
+    }
+}
+
+// Tracks the last known status of every relay and turns changes into notifications
+pub(crate) struct RelayMonitor {
+    state: Mutex<RelayMonitorState>,
+}
+
+impl RelayMonitor {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(RelayMonitorState::default()),
+        }
+    }
+
+    // Attached to the next transition observed for the relay
+    pub(crate) fn set_reason(&self, url: &str, reason: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .reasons
+            .insert(url.to_string(), reason.to_string());
+    }
+
+    // The delegate first receives the current status of every known relay
+    pub(crate) fn register(
+        &self,
+        delegate: Arc<dyn FatCrabRelayNotifDelegate>,
+    ) -> Vec<FatCrabRelayStatusNotifStruct> {
+        let mut state = self.state.lock().unwrap();
+        state.delegate = Some(delegate);
+        state
+            .statuses
+            .iter()
+            .map(|(url, status)| FatCrabRelayStatusNotifStruct {
+                url: url.clone(),
+                previous_status: None,
+                status: *status,
+                reason: None,
+                timestamp: now_timestamp(),
+            })
+            .collect()
+    }
+
+    pub(crate) fn unregister(&self) {
+        self.state.lock().unwrap().delegate = None;
+    }
+
+    pub(crate) fn set_policy(&self, policy: FatCrabRelayReconnectPolicy) {
+        let mut state = self.state.lock().unwrap();
+        state.policy = policy;
+        state.backoffs.clear();
+    }
+
+    // A relay added again after being removed is reconnected again
+    pub(crate) fn remember(&self, url: &str) {
+        self.state.lock().unwrap().removed.remove(url);
+    }
+
+    // A relay removed by the app is never reconnected
+    pub(crate) fn forget(&self, url: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.removed.insert(url.to_string());
+        state.backoffs.remove(url);
+        state.health.remove(url);
+    }
+
+    pub(crate) fn health(&self, url: &str) -> RelayHealth {
+        self.state
+            .lock()
+            .unwrap()
+            .health
+            .get(url)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Disconnected relays whose backoff has run out, each pushed back for its next attempt
+    fn due_reconnects(&self) -> Vec<String> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        if !state.policy.enabled {
+            return Vec::new();
+        }
+        let now = Instant::now();
+        let mut due = Vec::new();
+
+        for (url, status) in state.statuses.iter() {
+            if *status != RelayStatus::Disconnected || state.removed.contains(url) {
+                continue;
+            }
+            let policy = &state.policy;
+            let backoff = state
+                .backoffs
+                .entry(url.clone())
+                .or_insert_with(|| Backoff {
+                    attempt: 0,
+                    next_attempt_at: now + policy.delay(0),
+                });
+            if now < backoff.next_attempt_at {
+                continue;
+            }
+            backoff.attempt += 1;
+            backoff.next_attempt_at = now + policy.delay(backoff.attempt);
+            due.push(url.clone());
+        }
+        due
+    }
+
+    pub(crate) fn delegate(&self) -> Option<Arc<dyn FatCrabRelayNotifDelegate>> {
+        self.state.lock().unwrap().delegate.clone()
+    }
+
+    // Compares a fresh snapshot against the last one. Relays no longer listed are reported
+    // as Terminated, as that is the last status the Trading Engine gives a removed relay.
+    pub(crate) fn observe(
+        &self,
+        relays: Vec<(String, RelayStatus)>,
+    ) -> Vec<FatCrabRelayStatusNotifStruct> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let mut notifs = Vec::new();
+        let mut current = HashMap::new();
+
+        for (url, status) in relays {
+            let previous_status = state.statuses.remove(&url);
+            if previous_status != Some(status) {
+                let health = state.health.entry(url.clone()).or_default();
+                match status {
+                    RelayStatus::Connected => {
+                        health.last_connected_at = Some(now_timestamp());
+                        health.consecutive_failures = 0;
+                        state.backoffs.remove(&url);
+                    }
+                    RelayStatus::Disconnected => health.consecutive_failures += 1,
+                    _ => {}
+                }
+                notifs.push(FatCrabRelayStatusNotifStruct {
+                    url: url.clone(),
+                    previous_status,
+                    status,
+                    reason: state.reasons.remove(&url),
+                    timestamp: now_timestamp(),
+                });
+            }
+            current.insert(url, status);
+        }
+        for (url, previous_status) in state.statuses.drain() {
+            if previous_status != RelayStatus::Terminated {
+                notifs.push(FatCrabRelayStatusNotifStruct {
+                    url: url.clone(),
+                    previous_status: Some(previous_status),
+                    status: RelayStatus::Terminated,
+                    reason: state.reasons.remove(&url),
+                    timestamp: now_timestamp(),
+                });
+            }
+        }
+
+        state.statuses = current;
+        notifs
+    }
+}
+
+// The Trading Engine has no relay status notifications, so changes are found by polling.
+// Anything that comes and goes between two polls is not seen.
+// The watcher only holds a weak reference and stops once the Trader is dropped.
+pub(crate) fn spawn_relay_status_watcher(inner: Weak<InnerTrader>, ctx: Arc<TraderContext>) {
+    RUNTIME.spawn(async move {
+        loop {
+            let relays = match inner.upgrade() {
+                Some(inner) => inner.get_relays().await,
+                None => break,
+            };
+            let relays = relays
+                .into_iter()
+                .map(|relay| (relay.url.to_string(), relay.status))
+                .collect();
+
+            let notifs = ctx.relay_monitor.observe(relays);
+            reconnect_due_relays(&inner, &ctx).await;
+            release_ended_trade_relays(&inner, &ctx).await;
+            let delegate = ctx.relay_monitor.delegate();
+            for notif in notifs {
+                if let Some(delegate) = delegate.clone() {
+                    let notif = notif.clone();
+                    ctx.notif_hub
+                        .run_on_dispatcher(move || delegate.on_relay_status_notif(notif));
+                }
+                ctx.notif_hub.emit(FatCrabEvent::RelayStatus {
+                    relay_status_notif: notif,
+                });
+            }
+            tokio::time::sleep(RELAY_STATUS_POLL_INTERVAL).await;
+        }
+    });
+}
+
+// Relays connected for a trade are let go once the trade has a history record
+async fn release_ended_trade_relays(inner: &Weak<InnerTrader>, ctx: &TraderContext) {
+    for trade_uuid in ctx.nip65_client.tracked_trades() {
+        if !ctx.trade_history.has_record(&trade_uuid) {
+            continue;
+        }
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        release_trade_relays(&inner, ctx, &trade_uuid).await;
+    }
+}
+
+async fn reconnect_due_relays(inner: &Weak<InnerTrader>, ctx: &TraderContext) {
+    let due = ctx.relay_monitor.due_reconnects();
+    if due.is_empty() {
+        return;
+    }
+    let Some(inner) = inner.upgrade() else {
+        return;
+    };
+    for url in due.iter() {
+        ctx.relay_monitor
+            .set_reason(url, RELAY_REASON_AUTO_RECONNECT);
+    }
+    // Reconnects every relay, the Trading Engine has no way to reconnect only the due ones
+    if let Err(error) = inner.reconnect().await {
+        warn!(
+            "Failed to reconnect relays {} - {}",
+            due.join(", "),
+            FatCrabError::from(error)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(jitter: f64) -> FatCrabRelayReconnectPolicy {
+        FatCrabRelayReconnectPolicy {
+            enabled: true,
+            initial_delay_ms: 1_000,
+            max_delay_ms: 10_000,
+            jitter,
+        }
+    }
+
+    fn snapshot(relays: &[(&str, RelayStatus)]) -> Vec<(String, RelayStatus)> {
+        relays
+            .iter()
+            .map(|(url, status)| (url.to_string(), *status))
+            .collect()
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_maximum() {
+        let policy = policy(0.0);
+        let delays: Vec<u64> = (0..6)
+            .map(|attempt| policy.delay(attempt).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, vec![1_000, 2_000, 4_000, 8_000, 10_000, 10_000]);
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(10_000));
+    }
+
+    #[test]
+    fn delay_stays_within_the_jitter() {
+        let policy = policy(0.2);
+        for _ in 0..100 {
+            let delay_ms = policy.delay(1).as_millis() as u64;
+            assert!((1_600..=2_400).contains(&delay_ms), "{}", delay_ms);
+        }
+    }
+
+    #[test]
+    fn delay_clamps_the_jitter() {
+        let policy = policy(5.0);
+        for _ in 0..100 {
+            assert!(policy.delay(0) <= Duration::from_millis(2_000));
+        }
+    }
+
+    #[test]
+    fn observe_reports_only_changes() {
+        let monitor = RelayMonitor::new();
+        let notifs = monitor.observe(snapshot(&[("wss://a", RelayStatus::Connecting)]));
+        assert_eq!(notifs.len(), 1);
+        assert!(notifs[0].previous_status.is_none());
+        assert!(notifs[0].status == RelayStatus::Connecting);
+
+        assert!(monitor
+            .observe(snapshot(&[("wss://a", RelayStatus::Connecting)]))
+            .is_empty());
+
+        let notifs = monitor.observe(snapshot(&[("wss://a", RelayStatus::Connected)]));
+        assert_eq!(notifs.len(), 1);
+        assert!(notifs[0].previous_status == Some(RelayStatus::Connecting));
+        assert!(notifs[0].status == RelayStatus::Connected);
+        assert!(monitor.health("wss://a").last_connected_at.is_some());
+    }
+
+    #[test]
+    fn observe_attaches_the_reason_once() {
+        let monitor = RelayMonitor::new();
+        monitor.set_reason("wss://a", RELAY_REASON_ADDED);
+        let notifs = monitor.observe(snapshot(&[("wss://a", RelayStatus::Connecting)]));
+        assert_eq!(notifs[0].reason.as_deref(), Some(RELAY_REASON_ADDED));
+
+        let notifs = monitor.observe(snapshot(&[("wss://a", RelayStatus::Connected)]));
+        assert!(notifs[0].reason.is_none());
+    }
+
+    #[test]
+    fn observe_counts_each_disconnect_once() {
+        let monitor = RelayMonitor::new();
+        monitor.observe(snapshot(&[("wss://a", RelayStatus::Connected)]));
+        monitor.observe(snapshot(&[("wss://a", RelayStatus::Disconnected)]));
+        monitor.observe(snapshot(&[("wss://a", RelayStatus::Disconnected)]));
+        assert_eq!(monitor.health("wss://a").consecutive_failures, 1);
+
+        monitor.observe(snapshot(&[("wss://a", RelayStatus::Connecting)]));
+        monitor.observe(snapshot(&[("wss://a", RelayStatus::Disconnected)]));
+        assert_eq!(monitor.health("wss://a").consecutive_failures, 2);
+
+        monitor.observe(snapshot(&[("wss://a", RelayStatus::Connected)]));
+        assert_eq!(monitor.health("wss://a").consecutive_failures, 0);
+    }
+
+    #[test]
+    fn observe_reports_unlisted_relays_as_terminated() {
+        let monitor = RelayMonitor::new();
+        monitor.observe(snapshot(&[
+            ("wss://a", RelayStatus::Connected),
+            ("wss://b", RelayStatus::Connected),
+        ]));
+        monitor.set_reason("wss://b", RELAY_REASON_REMOVED);
+        let notifs = monitor.observe(snapshot(&[("wss://a", RelayStatus::Connected)]));
+        assert_eq!(notifs.len(), 1);
+        assert_eq!(notifs[0].url, "wss://b");
+        assert!(notifs[0].previous_status == Some(RelayStatus::Connected));
+        assert!(notifs[0].status == RelayStatus::Terminated);
+        assert_eq!(notifs[0].reason.as_deref(), Some(RELAY_REASON_REMOVED));
+
+        assert!(monitor
+            .observe(snapshot(&[("wss://a", RelayStatus::Connected)]))
+            .is_empty());
+    }
+}
@@ -1,5 +1,5 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::sync::Arc;
-use std::thread::spawn;
 
 pub use fatcrab_trading::taker::FatCrabTakerState;
 use fatcrab_trading::taker::{FatCrabTakerAccess, FatCrabTakerNotif, TakerBuy, TakerSell};
@@ -8,32 +8,415 @@ use fatcrab_trading::taker::{
     FatCrabTakerNotifTradeRspStruct as InnerTakerNotifTradeRspStruct,
 };
 use tokio::sync::mpsc;
+use tracing::{warn, Instrument};
 
-use crate::order::FatCrabOrderEnvelope;
+use crate::journal::FatCrabJournalEntry;
+use crate::logging::trade_span;
+use crate::nip65::spawn_connect_trade_relays;
+use crate::notif::{
+    peer_notif_key, trade_rsp_notif_key, FatCrabNotifBufferConfig, NotifDelegate, TradeNotif,
+};
+use crate::order::{FatCrabOrderEnvelope, FatCrabOrderType};
 use crate::peer::FatCrabPeerEnvelope;
-use crate::trade_rsp::FatCrabTradeRspEnvelope;
+use crate::persist::now_timestamp;
+use crate::trade::{FatCrabTrade, FatCrabTradeRole, FatCrabTradeState};
+use crate::trade_rsp::{FatCrabTradeRsp, FatCrabTradeRspEnvelope};
+use crate::trader::TraderContext;
 use crate::types::FatCrabTakerNotifDelegate;
 use crate::{error::FatCrabError, RUNTIME};
 
+// Only sits between the Trading Engine and the hub, delegates are buffered as they register
 const TAKER_NOTIF_CHANNEL_SIZE: usize = 10;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FatCrabTakerAction {
+    TakeOrder,
+    ReleaseNotifyPeer,
+    CheckBtcTxConfirmation,
+    NotifyPeer,
+    TradeComplete,
+}
+
+impl Display for FatCrabTakerAction {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let action_string = match self {
+            FatCrabTakerAction::TakeOrder => "TakeOrder",
+            FatCrabTakerAction::ReleaseNotifyPeer => "ReleaseNotifyPeer",
+            FatCrabTakerAction::CheckBtcTxConfirmation => "CheckBtcTxConfirmation",
+            FatCrabTakerAction::NotifyPeer => "NotifyPeer",
+            FatCrabTakerAction::TradeComplete => "TradeComplete",
+        };
+        write!(f, "{}", action_string)
+    }
+}
+
+pub(crate) fn taker_state_name(state: &FatCrabTakerState) -> &'static str {
+    match state {
+        FatCrabTakerState::New => "New",
+        FatCrabTakerState::SubmittedOffer => "SubmittedOffer",
+        FatCrabTakerState::OfferAccepted => "OfferAccepted",
+        FatCrabTakerState::OfferRejected => "OfferRejected",
+        FatCrabTakerState::NotifiedOutbound => "NotifiedOutbound",
+        FatCrabTakerState::InboundBtcNotified => "InboundBtcNotified",
+        FatCrabTakerState::InboundFcNotified => "InboundFcNotified",
+        FatCrabTakerState::TradeCompleted => "TradeCompleted",
+    }
+}
+
+// Buy Taker waits for BTC from the Maker, confirms it, then sends the Fatcrab
+fn buy_taker_allowed_actions(state: &FatCrabTakerState) -> Vec<FatCrabTakerAction> {
+    match state {
+        FatCrabTakerState::New => vec![FatCrabTakerAction::TakeOrder],
+        FatCrabTakerState::InboundBtcNotified => vec![
+            FatCrabTakerAction::CheckBtcTxConfirmation,
+            FatCrabTakerAction::NotifyPeer,
+        ],
+        FatCrabTakerState::NotifiedOutbound => vec![
+            FatCrabTakerAction::CheckBtcTxConfirmation,
+            FatCrabTakerAction::TradeComplete,
+        ],
+        FatCrabTakerState::SubmittedOffer
+        | FatCrabTakerState::OfferAccepted
+        | FatCrabTakerState::OfferRejected
+        | FatCrabTakerState::InboundFcNotified
+        | FatCrabTakerState::TradeCompleted => vec![],
+    }
+}
+
+// Sell Taker sends BTC first once the offer is accepted, then waits for the Fatcrab from the Maker
+fn sell_taker_allowed_actions(state: &FatCrabTakerState) -> Vec<FatCrabTakerAction> {
+    match state {
+        FatCrabTakerState::New => vec![FatCrabTakerAction::TakeOrder],
+        FatCrabTakerState::OfferAccepted => vec![FatCrabTakerAction::ReleaseNotifyPeer],
+        FatCrabTakerState::InboundFcNotified => vec![FatCrabTakerAction::TradeComplete],
+        FatCrabTakerState::SubmittedOffer
+        | FatCrabTakerState::OfferRejected
+        | FatCrabTakerState::NotifiedOutbound
+        | FatCrabTakerState::InboundBtcNotified
+        | FatCrabTakerState::TradeCompleted => vec![],
+    }
+}
+
+fn check_taker_action(
+    allowed_actions: Vec<FatCrabTakerAction>,
+    state: &FatCrabTakerState,
+    action: FatCrabTakerAction,
+) -> Result<(), FatCrabError> {
+    if allowed_actions.contains(&action) {
+        return Ok(());
+    }
+    Err(FatCrabError::InvalidStateTransition {
+        from: taker_state_name(state).to_string(),
+        action: action.to_string(),
+    })
+}
+
+fn journal_taker_transition(
+    ctx: &TraderContext,
+    trade_uuid: &str,
+    trigger: impl ToString,
+    from: Option<&FatCrabTakerState>,
+    result: &Result<FatCrabTakerState, FatCrabError>,
+) {
+    let from = from.map(taker_state_name);
+    let entry = match result {
+        Ok(to) => {
+            ctx.trade_timestamps.touch(trade_uuid, taker_state_name(to));
+            FatCrabJournalEntry::new(trigger, from, Some(taker_state_name(to)), None)
+        }
+        Err(error) => FatCrabJournalEntry::new(trigger, from, None, Some(error)),
+    };
+    ctx.trade_journal.append(trade_uuid, entry);
+}
+
+fn journal_taker_notif(
+    ctx: &TraderContext,
+    trade_uuid: &str,
+    trigger: &str,
+    to: &FatCrabTakerState,
+) {
+    let from = ctx.trade_journal.last_state(trade_uuid);
+    let to = taker_state_name(to);
+    ctx.trade_timestamps.touch(trade_uuid, to);
+    let entry = FatCrabJournalEntry::new(trigger, from.as_deref(), Some(to), None);
+    ctx.trade_journal.append(trade_uuid, entry);
+}
+
+fn spawn_taker_notif_forwarder(
+    ctx: Arc<TraderContext>,
+    trade_uuid: String,
+    order_type: FatCrabOrderType,
+    mut rx: mpsc::Receiver<FatCrabTakerNotif>,
+) {
+    let span = trade_span(&trade_uuid);
+    let forward = async move {
+        while let Some(notif) = rx.recv().await {
+            // Publishing waits for room in the delegate's buffer under the Block policy
+            match notif {
+                FatCrabTakerNotif::TradeRsp(trade_rsp_notif) => {
+                    journal_taker_notif(&ctx, &trade_uuid, "TradeResponse", &trade_rsp_notif.state);
+                    let trade_rsp_envelope =
+                        FatCrabTradeRspEnvelope::from(trade_rsp_notif.trade_rsp_envelope);
+                    ctx.notif_hub
+                        .publish(&trade_uuid, trade_rsp_notif_key(), |seq| {
+                            TradeNotif::TakerTradeRsp(FatCrabTakerNotifTradeRspStruct::new(
+                                trade_uuid.clone(),
+                                order_type.clone(),
+                                seq,
+                                trade_rsp_notif.state,
+                                Arc::new(trade_rsp_envelope),
+                            ))
+                        })
+                        .await;
+                }
+                FatCrabTakerNotif::Peer(peer_notif) => {
+                    journal_taker_notif(&ctx, &trade_uuid, "PeerMessage", &peer_notif.state);
+                    let peer_envelope = FatCrabPeerEnvelope::from(peer_notif.peer_envelope);
+                    let key = peer_notif_key(&peer_envelope);
+                    ctx.notif_hub
+                        .publish(&trade_uuid, key, |seq| {
+                            TradeNotif::TakerPeer(FatCrabTakerNotifPeerStruct::new(
+                                trade_uuid.clone(),
+                                order_type.clone(),
+                                seq,
+                                peer_notif.state,
+                                Arc::new(peer_envelope),
+                            ))
+                        })
+                        .await;
+                }
+            }
+        }
+    };
+    RUNTIME.spawn(forward.instrument(span));
+}
+
+// The state a notification moved the trade to follows from the notification itself,
+// the trade may well have moved on since it arrived
+fn taker_trade_rsp_notif_state(trade_rsp_envelope: &FatCrabTradeRspEnvelope) -> FatCrabTakerState {
+    match trade_rsp_envelope.trade_rsp() {
+        FatCrabTradeRsp::Accept { .. } => FatCrabTakerState::OfferAccepted,
+        FatCrabTradeRsp::Reject => FatCrabTakerState::OfferRejected,
+    }
+}
+
+fn taker_peer_notif_state(order_type: &FatCrabOrderType) -> FatCrabTakerState {
+    match order_type {
+        FatCrabOrderType::Buy => FatCrabTakerState::InboundBtcNotified,
+        FatCrabOrderType::Sell => FatCrabTakerState::InboundFcNotified,
+    }
+}
+
+fn replay_taker_notifs(
+    ctx: &TraderContext,
+    trade_uuid: &str,
+    order_type: FatCrabOrderType,
+    state: FatCrabTakerState,
+    trade_rsp_envelope: Option<Arc<FatCrabTradeRspEnvelope>>,
+    peer_envelope: Option<Arc<FatCrabPeerEnvelope>>,
+) {
+    // Transitions made while the app was not running only show up as a different state
+    if ctx.trade_journal.last_state(trade_uuid).as_deref() != Some(taker_state_name(&state)) {
+        journal_taker_notif(ctx, trade_uuid, "Recovered", &state);
+    }
+    let trade_state: FatCrabTradeState = (&state).into();
+    if matches!(trade_state, FatCrabTradeState::Completed) {
+        ctx.notif_hub.trade_ended(trade_uuid);
+        return;
+    }
+
+    if let Some(trade_rsp_envelope) = trade_rsp_envelope {
+        let state = taker_trade_rsp_notif_state(&trade_rsp_envelope);
+        ctx.notif_hub
+            .replay(trade_uuid, trade_rsp_notif_key(), |seq| {
+                TradeNotif::TakerTradeRsp(FatCrabTakerNotifTradeRspStruct::new(
+                    trade_uuid.to_string(),
+                    order_type.clone(),
+                    seq,
+                    state,
+                    trade_rsp_envelope,
+                ))
+            });
+    }
+    if let Some(peer_envelope) = peer_envelope {
+        let key = peer_notif_key(&peer_envelope);
+        let state = taker_peer_notif_state(&order_type);
+        ctx.notif_hub.replay(trade_uuid, key, |seq| {
+            TradeNotif::TakerPeer(FatCrabTakerNotifPeerStruct::new(
+                trade_uuid.to_string(),
+                order_type,
+                seq,
+                state,
+                peer_envelope,
+            ))
+        });
+    }
+}
+
 pub struct FatCrabBuyTaker {
     inner: FatCrabTakerAccess<TakerBuy>,
+    trade_uuid: String,
+    ctx: Arc<TraderContext>,
 }
 
 pub struct FatCrabSellTaker {
     inner: FatCrabTakerAccess<TakerSell>,
+    trade_uuid: String,
+    ctx: Arc<TraderContext>,
 }
 
 impl FatCrabBuyTaker {
-    pub(crate) fn new(taker: FatCrabTakerAccess<TakerBuy>) -> Self {
-        Self { inner: taker }
+    pub(crate) fn new(
+        taker: FatCrabTakerAccess<TakerBuy>,
+        trade_uuid: String,
+        ctx: Arc<TraderContext>,
+    ) -> Self {
+        Self {
+            inner: taker,
+            trade_uuid,
+            ctx,
+        }
+    }
+
+    // Notifications are captured from the moment the Trader hands out a trade, delegate or not
+    pub(crate) fn attach_notifs(&self) {
+        let _span = trade_span(&self.trade_uuid).entered();
+        if !self.ctx.notif_hub.begin_attach(&self.trade_uuid) {
+            return;
+        }
+        let (tx, rx) = mpsc::channel(TAKER_NOTIF_CHANNEL_SIZE);
+        if let Err(error) = RUNTIME.block_on(async { self.inner.register_notif_tx(tx).await }) {
+            warn!(
+                "Failed to capture notifications for trade {} - {}",
+                self.trade_uuid,
+                FatCrabError::from(error)
+            );
+            self.ctx.notif_hub.abort_attach(&self.trade_uuid);
+            return;
+        }
+        spawn_taker_notif_forwarder(
+            self.ctx.clone(),
+            self.trade_uuid.clone(),
+            FatCrabOrderType::Buy,
+            rx,
+        );
+
+        // Whatever arrived while the app was not running is only known to the Trading Engine
+        match (
+            self.get_state(),
+            self.query_trade_rsp(),
+            self.query_peer_msg(),
+        ) {
+            (Ok(state), Ok(trade_rsp_envelope), Ok(peer_envelope)) => replay_taker_notifs(
+                &self.ctx,
+                &self.trade_uuid,
+                FatCrabOrderType::Buy,
+                state,
+                trade_rsp_envelope,
+                peer_envelope,
+            ),
+            _ => warn!(
+                "Failed to recover notifications for trade {}",
+                self.trade_uuid
+            ),
+        }
+
+        // The peer is the order's maker, reached on its own relays for the whole trade
+        if let Ok(order_envelope) = self.get_order_details() {
+            spawn_connect_trade_relays(
+                self.ctx.clone(),
+                self.trade_uuid.clone(),
+                order_envelope.pubkey(),
+            );
+        }
+    }
+
+    pub fn allowed_actions(&self) -> Result<Vec<FatCrabTakerAction>, FatCrabError> {
+        let state = self.get_state()?;
+        Ok(buy_taker_allowed_actions(&state))
+    }
+
+    fn check_action(&self, action: FatCrabTakerAction) -> Result<FatCrabTakerState, FatCrabError> {
+        let state = self.get_state()?;
+        if let Err(error) = check_taker_action(buy_taker_allowed_actions(&state), &state, action) {
+            let from = taker_state_name(&state);
+            let entry = FatCrabJournalEntry::new(action, Some(from), None, Some(&error));
+            self.ctx.trade_journal.append(&self.trade_uuid, entry);
+            return Err(error);
+        }
+        Ok(state)
+    }
+
+    fn journal_action(
+        &self,
+        action: FatCrabTakerAction,
+        from: &FatCrabTakerState,
+        result: Result<FatCrabTakerState, FatCrabError>,
+    ) -> Result<FatCrabTakerState, FatCrabError> {
+        journal_taker_transition(&self.ctx, &self.trade_uuid, action, Some(from), &result);
+        result
+    }
+
+    pub fn get_trade_journal(&self) -> Result<Vec<FatCrabJournalEntry>, FatCrabError> {
+        self.ctx.trade_journal.entries(&self.trade_uuid)
+    }
+
+    fn record_history(&self, state: &FatCrabTakerState) -> Result<(), FatCrabError> {
+        let trade_state: FatCrabTradeState = state.into();
+        if !matches!(trade_state, FatCrabTradeState::Completed) {
+            return Ok(());
+        }
+        self.ctx.notif_hub.trade_ended(&self.trade_uuid);
+        if let Some(btc_txid) = self.get_peer_btc_txid()? {
+            self.ctx
+                .trade_history
+                .observe_peer_btc_txid(&self.trade_uuid, btc_txid);
+            self.observe_btc_confirmations();
+        }
+        let trade = self.make_trade(state)?;
+        self.ctx.trade_history.record(&trade);
+        Ok(())
+    }
+
+    // Read once the trade has ended, so check_action is not asked
+    fn observe_btc_confirmations(&self) {
+        match RUNTIME.block_on(async { self.inner.check_btc_tx_confirmation().await }) {
+            Ok(confirmations) => self
+                .ctx
+                .trade_history
+                .observe_btc_confirmations(&self.trade_uuid, confirmations),
+            Err(error) => warn!(
+                "Failed to read BTC confirmations for trade {} - {}",
+                self.trade_uuid,
+                FatCrabError::from(error)
+            ),
+        }
+    }
+
+    // As listed by the Trader
+    pub(crate) fn trade(&self) -> Result<FatCrabTrade, FatCrabError> {
+        let state = self.get_state()?;
+        self.make_trade(&state)
+    }
+
+    fn make_trade(&self, state: &FatCrabTakerState) -> Result<FatCrabTrade, FatCrabError> {
+        let order_envelope = self.get_order_details()?;
+        Ok(self.ctx.trade_timestamps.make_trade(
+            self.trade_uuid.clone(),
+            FatCrabTradeRole::Taker,
+            order_envelope.order(),
+            state.into(),
+            Some(order_envelope.pubkey()),
+        ))
     }
 
     pub fn take_order(&self) -> Result<FatCrabTakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabTakerAction::TakeOrder)?;
+        let result = RUNTIME
             .block_on(async { self.inner.take_order().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        self.journal_action(FatCrabTakerAction::TakeOrder, &from, result)
     }
 
     pub fn get_order_details(&self) -> Result<Arc<FatCrabOrderEnvelope>, FatCrabError> {
@@ -83,67 +466,199 @@ impl FatCrabBuyTaker {
     }
 
     pub fn notify_peer(&self, txid: String) -> Result<FatCrabTakerState, FatCrabError> {
-        RUNTIME
-            .block_on(async { self.inner.notify_peer(txid).await })
-            .map_err(|e| e.into())
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabTakerAction::NotifyPeer)?;
+        let result = RUNTIME
+            .block_on(async { self.inner.notify_peer(txid.clone()).await })
+            .map_err(|e| e.into());
+        let state = self.journal_action(FatCrabTakerAction::NotifyPeer, &from, result)?;
+        self.ctx
+            .trade_history
+            .observe_fatcrab_txid(&self.trade_uuid, txid);
+        Ok(state)
     }
 
     pub fn check_btc_tx_confirmation(&self) -> Result<u32, FatCrabError> {
+        let _span = trade_span(&self.trade_uuid).entered();
+        self.check_action(FatCrabTakerAction::CheckBtcTxConfirmation)?;
         RUNTIME
             .block_on(async { self.inner.check_btc_tx_confirmation().await })
             .map_err(|e| e.into())
     }
 
     pub fn trade_complete(&self) -> Result<FatCrabTakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabTakerAction::TradeComplete)?;
+        let result = RUNTIME
             .block_on(async { self.inner.trade_complete().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        let state = self.journal_action(FatCrabTakerAction::TradeComplete, &from, result)?;
+        if let Err(error) = self.record_history(&state) {
+            warn!(
+                "Failed to record history for trade {} - {}",
+                self.trade_uuid, error
+            );
+        }
+        Ok(state)
     }
 
     pub fn register_notif_delegate(
         &self,
         delegate: Arc<dyn FatCrabTakerNotifDelegate>,
+        buffer_config: Option<FatCrabNotifBufferConfig>,
     ) -> Result<(), FatCrabError> {
-        RUNTIME.block_on(async {
-            _ = self.inner.unregister_notif_tx().await;
-        });
-
-        let (tx, mut rx) = mpsc::channel(TAKER_NOTIF_CHANNEL_SIZE);
-        spawn(move || loop {
-            match rx.blocking_recv() {
-                Some(notif) => match notif {
-                    FatCrabTakerNotif::TradeRsp(trade_rsp_notif) => {
-                        delegate.on_taker_trade_rsp_notif(trade_rsp_notif.into());
-                    }
-                    FatCrabTakerNotif::Peer(peer_notif) => {
-                        delegate.on_taker_peer_notif(peer_notif.into());
-                    }
-                },
-                None => break,
-            }
-        });
-
-        RUNTIME
-            .block_on(async { self.inner.register_notif_tx(tx).await })
-            .map_err(|e| e.into())
+        self.attach_notifs();
+        self.ctx.notif_hub.register(
+            &self.trade_uuid,
+            NotifDelegate::Taker(delegate),
+            buffer_config.unwrap_or_default(),
+        );
+        Ok(())
     }
 
     pub fn unregister_notif_delegate(&self) -> Result<(), FatCrabError> {
-        RUNTIME
-            .block_on(async { self.inner.unregister_notif_tx().await })
-            .map_err(|e| e.into())
+        self.ctx.notif_hub.unregister(&self.trade_uuid);
+        Ok(())
+    }
+
+    pub fn acknowledge_notifs(&self, seq: u64) -> Result<(), FatCrabError> {
+        self.ctx.notif_hub.acknowledge(&self.trade_uuid, seq)
     }
 }
 
 impl FatCrabSellTaker {
-    pub(crate) fn new(taker: FatCrabTakerAccess<TakerSell>) -> Self {
-        Self { inner: taker }
+    pub(crate) fn new(
+        taker: FatCrabTakerAccess<TakerSell>,
+        trade_uuid: String,
+        ctx: Arc<TraderContext>,
+    ) -> Self {
+        Self {
+            inner: taker,
+            trade_uuid,
+            ctx,
+        }
+    }
+
+    // Notifications are captured from the moment the Trader hands out a trade, delegate or not
+    pub(crate) fn attach_notifs(&self) {
+        let _span = trade_span(&self.trade_uuid).entered();
+        if !self.ctx.notif_hub.begin_attach(&self.trade_uuid) {
+            return;
+        }
+        let (tx, rx) = mpsc::channel(TAKER_NOTIF_CHANNEL_SIZE);
+        if let Err(error) = RUNTIME.block_on(async { self.inner.register_notif_tx(tx).await }) {
+            warn!(
+                "Failed to capture notifications for trade {} - {}",
+                self.trade_uuid,
+                FatCrabError::from(error)
+            );
+            self.ctx.notif_hub.abort_attach(&self.trade_uuid);
+            return;
+        }
+        spawn_taker_notif_forwarder(
+            self.ctx.clone(),
+            self.trade_uuid.clone(),
+            FatCrabOrderType::Sell,
+            rx,
+        );
+
+        // Whatever arrived while the app was not running is only known to the Trading Engine
+        // A Sell Taker's trade response cannot be queried back, only the peer message can
+        match (self.get_state(), self.query_peer_msg()) {
+            (Ok(state), Ok(peer_envelope)) => replay_taker_notifs(
+                &self.ctx,
+                &self.trade_uuid,
+                FatCrabOrderType::Sell,
+                state,
+                None,
+                peer_envelope,
+            ),
+            _ => warn!(
+                "Failed to recover notifications for trade {}",
+                self.trade_uuid
+            ),
+        }
+
+        // The peer is the order's maker, reached on its own relays for the whole trade
+        if let Ok(order_envelope) = self.get_order_details() {
+            spawn_connect_trade_relays(
+                self.ctx.clone(),
+                self.trade_uuid.clone(),
+                order_envelope.pubkey(),
+            );
+        }
+    }
+
+    pub fn allowed_actions(&self) -> Result<Vec<FatCrabTakerAction>, FatCrabError> {
+        let state = self.get_state()?;
+        Ok(sell_taker_allowed_actions(&state))
+    }
+
+    fn check_action(&self, action: FatCrabTakerAction) -> Result<FatCrabTakerState, FatCrabError> {
+        let state = self.get_state()?;
+        if let Err(error) = check_taker_action(sell_taker_allowed_actions(&state), &state, action) {
+            let from = taker_state_name(&state);
+            let entry = FatCrabJournalEntry::new(action, Some(from), None, Some(&error));
+            self.ctx.trade_journal.append(&self.trade_uuid, entry);
+            return Err(error);
+        }
+        Ok(state)
+    }
+
+    fn journal_action(
+        &self,
+        action: FatCrabTakerAction,
+        from: &FatCrabTakerState,
+        result: Result<FatCrabTakerState, FatCrabError>,
+    ) -> Result<FatCrabTakerState, FatCrabError> {
+        journal_taker_transition(&self.ctx, &self.trade_uuid, action, Some(from), &result);
+        result
+    }
+
+    pub fn get_trade_journal(&self) -> Result<Vec<FatCrabJournalEntry>, FatCrabError> {
+        self.ctx.trade_journal.entries(&self.trade_uuid)
+    }
+
+    fn record_history(&self, state: &FatCrabTakerState) -> Result<(), FatCrabError> {
+        let trade_state: FatCrabTradeState = state.into();
+        if !matches!(trade_state, FatCrabTradeState::Completed) {
+            return Ok(());
+        }
+        self.ctx.notif_hub.trade_ended(&self.trade_uuid);
+        if let Some(peer_envelope) = self.query_peer_msg()? {
+            self.ctx
+                .trade_history
+                .observe_fatcrab_txid(&self.trade_uuid, peer_envelope.message().txid);
+        }
+        let trade = self.make_trade(state)?;
+        self.ctx.trade_history.record(&trade);
+        Ok(())
+    }
+
+    // As listed by the Trader
+    pub(crate) fn trade(&self) -> Result<FatCrabTrade, FatCrabError> {
+        let state = self.get_state()?;
+        self.make_trade(&state)
+    }
+
+    fn make_trade(&self, state: &FatCrabTakerState) -> Result<FatCrabTrade, FatCrabError> {
+        let order_envelope = self.get_order_details()?;
+        Ok(self.ctx.trade_timestamps.make_trade(
+            self.trade_uuid.clone(),
+            FatCrabTradeRole::Taker,
+            order_envelope.order(),
+            state.into(),
+            Some(order_envelope.pubkey()),
+        ))
     }
 
     pub fn take_order(&self) -> Result<FatCrabTakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabTakerAction::TakeOrder)?;
+        let result = RUNTIME
             .block_on(async { self.inner.take_order().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        self.journal_action(FatCrabTakerAction::TakeOrder, &from, result)
     }
 
     pub fn get_order_details(&self) -> Result<Arc<FatCrabOrderEnvelope>, FatCrabError> {
@@ -177,62 +692,79 @@ impl FatCrabSellTaker {
     }
 
     pub fn release_notify_peer(&self) -> Result<FatCrabTakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabTakerAction::ReleaseNotifyPeer)?;
+        let result = RUNTIME
             .block_on(async { self.inner.release_notify_peer().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        self.journal_action(FatCrabTakerAction::ReleaseNotifyPeer, &from, result)
     }
 
     pub fn trade_complete(&self) -> Result<FatCrabTakerState, FatCrabError> {
-        RUNTIME
+        let _span = trade_span(&self.trade_uuid).entered();
+        let from = self.check_action(FatCrabTakerAction::TradeComplete)?;
+        let result = RUNTIME
             .block_on(async { self.inner.trade_complete().await })
-            .map_err(|e| e.into())
+            .map_err(|e| e.into());
+        let state = self.journal_action(FatCrabTakerAction::TradeComplete, &from, result)?;
+        if let Err(error) = self.record_history(&state) {
+            warn!(
+                "Failed to record history for trade {} - {}",
+                self.trade_uuid, error
+            );
+        }
+        Ok(state)
     }
 
     pub fn register_notif_delegate(
         &self,
         delegate: Arc<dyn FatCrabTakerNotifDelegate>,
+        buffer_config: Option<FatCrabNotifBufferConfig>,
     ) -> Result<(), FatCrabError> {
-        RUNTIME.block_on(async {
-            _ = self.inner.unregister_notif_tx().await;
-        });
-
-        let (tx, mut rx) = mpsc::channel(TAKER_NOTIF_CHANNEL_SIZE);
-        spawn(move || loop {
-            match rx.blocking_recv() {
-                Some(notif) => match notif {
-                    FatCrabTakerNotif::TradeRsp(trade_rsp_notif) => {
-                        delegate.on_taker_trade_rsp_notif(trade_rsp_notif.into());
-                    }
-                    FatCrabTakerNotif::Peer(peer_notif) => {
-                        delegate.on_taker_peer_notif(peer_notif.into());
-                    }
-                },
-                None => break,
-            }
-        });
-
-        RUNTIME
-            .block_on(async { self.inner.register_notif_tx(tx).await })
-            .map_err(|e| e.into())
+        self.attach_notifs();
+        self.ctx.notif_hub.register(
+            &self.trade_uuid,
+            NotifDelegate::Taker(delegate),
+            buffer_config.unwrap_or_default(),
+        );
+        Ok(())
     }
 
     pub fn unregister_notif_delegate(&self) -> Result<(), FatCrabError> {
-        RUNTIME
-            .block_on(async { self.inner.unregister_notif_tx().await })
-            .map_err(|e| e.into())
+        self.ctx.notif_hub.unregister(&self.trade_uuid);
+        Ok(())
+    }
+
+    pub fn acknowledge_notifs(&self, seq: u64) -> Result<(), FatCrabError> {
+        self.ctx.notif_hub.acknowledge(&self.trade_uuid, seq)
     }
 }
 
+#[derive(Clone)]
 pub struct FatCrabTakerNotifTradeRspStruct {
+    pub trade_uuid: String,
+    pub order_type: FatCrabOrderType,
+    pub seq: u64,
+    pub timestamp: u64, // when the FFI captured it, in seconds since the Unix epoch
     pub state: FatCrabTakerState,
     pub trade_rsp_envelope: Arc<FatCrabTradeRspEnvelope>,
 }
 
-impl From<InnerTakerNotifTradeRspStruct> for FatCrabTakerNotifTradeRspStruct {
-    fn from(trade_rsp_notif: InnerTakerNotifTradeRspStruct) -> Self {
+impl FatCrabTakerNotifTradeRspStruct {
+    pub(crate) fn new(
+        trade_uuid: String,
+        order_type: FatCrabOrderType,
+        seq: u64,
+        state: FatCrabTakerState,
+        trade_rsp_envelope: Arc<FatCrabTradeRspEnvelope>,
+    ) -> Self {
         Self {
-            state: trade_rsp_notif.state,
-            trade_rsp_envelope: Arc::new(trade_rsp_notif.trade_rsp_envelope.into()),
+            trade_uuid,
+            order_type,
+            seq,
+            timestamp: now_timestamp(),
+            state,
+            trade_rsp_envelope,
         }
     }
 }
@@ -246,16 +778,31 @@ impl Into<InnerTakerNotifTradeRspStruct> for FatCrabTakerNotifTradeRspStruct {
     }
 }
 
+#[derive(Clone)]
 pub struct FatCrabTakerNotifPeerStruct {
+    pub trade_uuid: String,
+    pub order_type: FatCrabOrderType,
+    pub seq: u64,
+    pub timestamp: u64, // when the FFI captured it, in seconds since the Unix epoch
     pub state: FatCrabTakerState,
     pub peer_envelope: Arc<FatCrabPeerEnvelope>,
 }
 
-impl From<InnerTakerNotifPeerStruct> for FatCrabTakerNotifPeerStruct {
-    fn from(peer_notif: InnerTakerNotifPeerStruct) -> Self {
+impl FatCrabTakerNotifPeerStruct {
+    pub(crate) fn new(
+        trade_uuid: String,
+        order_type: FatCrabOrderType,
+        seq: u64,
+        state: FatCrabTakerState,
+        peer_envelope: Arc<FatCrabPeerEnvelope>,
+    ) -> Self {
         Self {
-            state: peer_notif.state,
-            peer_envelope: Arc::new(peer_notif.peer_envelope.into()),
+            trade_uuid,
+            order_type,
+            seq,
+            timestamp: now_timestamp(),
+            state,
+            peer_envelope,
         }
     }
 }
@@ -268,3 +815,95 @@ impl Into<InnerTakerNotifPeerStruct> for FatCrabTakerNotifPeerStruct {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use FatCrabTakerAction::*;
+
+    const ALL_ACTIONS: [FatCrabTakerAction; 5] = [
+        TakeOrder,
+        ReleaseNotifyPeer,
+        CheckBtcTxConfirmation,
+        NotifyPeer,
+        TradeComplete,
+    ];
+
+    #[test]
+    fn buy_taker_allowed_actions_follow_the_trade() {
+        let table = [
+            (FatCrabTakerState::New, vec![TakeOrder]),
+            (FatCrabTakerState::SubmittedOffer, vec![]),
+            (FatCrabTakerState::OfferAccepted, vec![]),
+            (FatCrabTakerState::OfferRejected, vec![]),
+            (
+                FatCrabTakerState::InboundBtcNotified,
+                vec![CheckBtcTxConfirmation, NotifyPeer],
+            ),
+            (FatCrabTakerState::InboundFcNotified, vec![]),
+            (
+                FatCrabTakerState::NotifiedOutbound,
+                vec![CheckBtcTxConfirmation, TradeComplete],
+            ),
+            (FatCrabTakerState::TradeCompleted, vec![]),
+        ];
+        for (state, expected) in table {
+            assert_eq!(
+                buy_taker_allowed_actions(&state),
+                expected,
+                "{}",
+                taker_state_name(&state)
+            );
+        }
+    }
+
+    #[test]
+    fn sell_taker_allowed_actions_follow_the_trade() {
+        let table = [
+            (FatCrabTakerState::New, vec![TakeOrder]),
+            (FatCrabTakerState::SubmittedOffer, vec![]),
+            (FatCrabTakerState::OfferAccepted, vec![ReleaseNotifyPeer]),
+            (FatCrabTakerState::OfferRejected, vec![]),
+            (FatCrabTakerState::InboundBtcNotified, vec![]),
+            (FatCrabTakerState::InboundFcNotified, vec![TradeComplete]),
+            (FatCrabTakerState::NotifiedOutbound, vec![]),
+            (FatCrabTakerState::TradeCompleted, vec![]),
+        ];
+        for (state, expected) in table {
+            assert_eq!(
+                sell_taker_allowed_actions(&state),
+                expected,
+                "{}",
+                taker_state_name(&state)
+            );
+        }
+    }
+
+    #[test]
+    fn check_taker_action_only_allows_listed_actions() {
+        let state = FatCrabTakerState::NotifiedOutbound;
+        for action in ALL_ACTIONS {
+            let result = check_taker_action(buy_taker_allowed_actions(&state), &state, action);
+            assert_eq!(
+                result.is_ok(),
+                action == CheckBtcTxConfirmation || action == TradeComplete,
+                "{}",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn check_taker_action_names_state_and_action() {
+        let state = FatCrabTakerState::SubmittedOffer;
+        let result = check_taker_action(sell_taker_allowed_actions(&state), &state, TakeOrder);
+        match result {
+            Err(FatCrabError::InvalidStateTransition { from, action }) => {
+                assert_eq!(from, "SubmittedOffer");
+                assert_eq!(action, "TakeOrder");
+            }
+            _ => panic!("TakeOrder allowed after submitting an offer"),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::journal::FatCrabJournalEntry;
+use crate::maker::FatCrabMakerState;
+use crate::order::{FatCrabOrder, FatCrabOrderType};
+use crate::persist::{ffi_data_dir, load_json, now_timestamp, save_json};
+use crate::taker::FatCrabTakerState;
+
+const TRADE_TIMESTAMPS_FILENAME: &str = "trade_timestamps.json";
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FatCrabTradeRole {
+    Maker,
+    Taker,
+}
+
+// Maker and Taker states normalized into a single role-agnostic progression
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FatCrabTradeState {
+    New,
+    Open,
+    OfferPending,
+    Accepted,
+    Rejected,
+    InboundNotified,
+    OutboundNotified,
+    Completed,
+    Cancelled,
+}
+
+impl From<&FatCrabMakerState> for FatCrabTradeState {
+    fn from(state: &FatCrabMakerState) -> Self {
+        match state {
+            FatCrabMakerState::New => FatCrabTradeState::New,
+            FatCrabMakerState::WaitingForOffers => FatCrabTradeState::Open,
+            FatCrabMakerState::ReceivedOffer => FatCrabTradeState::OfferPending,
+            FatCrabMakerState::AcceptedOffer => FatCrabTradeState::Accepted,
+            FatCrabMakerState::InboundBtcNotified => FatCrabTradeState::InboundNotified,
+            FatCrabMakerState::InboundFcNotified => FatCrabTradeState::InboundNotified,
+            FatCrabMakerState::NotifiedOutbound => FatCrabTradeState::OutboundNotified,
+            FatCrabMakerState::TradeCompleted => FatCrabTradeState::Completed,
+            FatCrabMakerState::TradeCancelled => FatCrabTradeState::Cancelled,
+        }
+    }
+}
+
+impl From<&FatCrabTakerState> for FatCrabTradeState {
+    fn from(state: &FatCrabTakerState) -> Self {
+        match state {
+            FatCrabTakerState::New => FatCrabTradeState::New,
+            FatCrabTakerState::SubmittedOffer => FatCrabTradeState::OfferPending,
+            FatCrabTakerState::OfferAccepted => FatCrabTradeState::Accepted,
+            FatCrabTakerState::OfferRejected => FatCrabTradeState::Rejected,
+            FatCrabTakerState::InboundBtcNotified => FatCrabTradeState::InboundNotified,
+            FatCrabTakerState::InboundFcNotified => FatCrabTradeState::InboundNotified,
+            FatCrabTakerState::NotifiedOutbound => FatCrabTradeState::OutboundNotified,
+            FatCrabTakerState::TradeCompleted => FatCrabTradeState::Completed,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FatCrabTrade {
+    pub trade_uuid: String,
+    pub role: FatCrabTradeRole,
+    pub order_type: FatCrabOrderType,
+    pub order: FatCrabOrder,
+    pub state: FatCrabTradeState,
+    pub peer_pubkey: Option<String>,
+    // 0 when the trade predates both its timestamps and its journal
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+pub struct FatCrabTradeFilter {
+    pub states: Option<Vec<FatCrabTradeState>>,
+    pub role: Option<FatCrabTradeRole>,
+    pub order_type: Option<FatCrabOrderType>,
+}
+
+impl FatCrabTradeFilter {
+    pub(crate) fn matches(&self, trade: &FatCrabTrade) -> bool {
+        if let Some(states) = &self.states {
+            if !states.contains(&trade.state) {
+                return false;
+            }
+        }
+        if let Some(role) = &self.role {
+            if *role != trade.role {
+                return false;
+            }
+        }
+        if let Some(order_type) = &self.order_type {
+            let same_type = matches!(
+                (order_type, &trade.order_type),
+                (FatCrabOrderType::Buy, FatCrabOrderType::Buy)
+                    | (FatCrabOrderType::Sell, FatCrabOrderType::Sell)
+            );
+            if !same_type {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct TradeTimes {
+    created_at: u64,
+    updated_at: u64,
+    state: String,
+}
+
+// The Trading Engine does not keep timestamps, so the FFI records when a trade
+// was first seen and when it was last observed changing state
+pub(crate) struct TradeTimestamps {
+    path: PathBuf,
+    times: Mutex<HashMap<String, TradeTimes>>,
+}
+
+impl TradeTimestamps {
+    pub(crate) fn new(app_dir_path: &str) -> Self {
+        let path = ffi_data_dir(app_dir_path).join(TRADE_TIMESTAMPS_FILENAME);
+        let times = load_json(&path);
+        Self {
+            path,
+            times: Mutex::new(times),
+        }
+    }
+
+    // Records the state a trade was observed entering
+    pub(crate) fn touch(&self, trade_uuid: &str, state: &str) {
+        let mut times = self.times.lock().unwrap();
+        let now = now_timestamp();
+        let mut changed = false;
+
+        let entry = times.entry(trade_uuid.to_string()).or_insert_with(|| {
+            changed = true;
+            TradeTimes {
+                created_at: now,
+                updated_at: now,
+                state: state.to_string(),
+            }
+        });
+        if entry.state != state {
+            entry.state = state.to_string();
+            entry.updated_at = now;
+            changed = true;
+        }
+
+        if changed {
+            if let Err(error) = save_json(&self.path, &*times) {
+                warn!("Failed to persist trade timestamps - {}", error);
+            }
+        }
+    }
+
+    // Trades known from before timestamps were recorded take them from their journal
+    pub(crate) fn backfill(&self, trade_uuid: &str, entries: &[FatCrabJournalEntry]) {
+        let mut times = self.times.lock().unwrap();
+        if times.contains_key(trade_uuid) {
+            return;
+        }
+        let Some(first) = entries.first() else {
+            return;
+        };
+        let last_transition = entries
+            .iter()
+            .rev()
+            .find_map(|entry| Some((entry.timestamp, entry.to_state.clone()?)));
+        let (updated_at, state) = last_transition.unwrap_or((first.timestamp, String::new()));
+        times.insert(
+            trade_uuid.to_string(),
+            TradeTimes {
+                created_at: first.timestamp,
+                updated_at,
+                state,
+            },
+        );
+        if let Err(error) = save_json(&self.path, &*times) {
+            warn!("Failed to persist trade timestamps - {}", error);
+        }
+    }
+
+    // Read only, the timestamps are recorded as the trade changes state
+    pub(crate) fn make_trade(
+        &self,
+        trade_uuid: String,
+        role: FatCrabTradeRole,
+        order: FatCrabOrder,
+        state: FatCrabTradeState,
+        peer_pubkey: Option<String>,
+    ) -> FatCrabTrade {
+        let (created_at, updated_at) = self
+            .times
+            .lock()
+            .unwrap()
+            .get(&trade_uuid)
+            .map(|times| (times.created_at, times.updated_at))
+            .unwrap_or((0, 0));
+        FatCrabTrade {
+            trade_uuid,
+            role,
+            order_type: order.order_type.clone(),
+            order,
+            state,
+            peer_pubkey,
+            created_at,
+            updated_at,
+        }
+    }
+}
@@ -1,31 +1,98 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 use bitcoin::Address;
 use secp256k1::SecretKey;
+use tracing::warn;
 use url::Url;
 
 pub use fatcrab_trading::common::Balances;
 use fatcrab_trading::{order::FatCrabOrderType, trader::FatCrabTrader as InnerTrader};
 
 use crate::error::FatCrabError;
+use crate::event::{EventQueue, FatCrabEvent};
+use crate::history::{FatCrabHistoryFormat, FatCrabTradeRecord, TradeHistory};
+use crate::journal::{FatCrabJournalEntry, TradeJournal};
 use crate::maker::{FatCrabBuyMaker, FatCrabSellMaker};
+use crate::nip11::{FatCrabRelayProbe, FatCrabRelayRequirements, RelayChecker};
+use crate::nip65::{connect_trade_relays, release_trade_relays, FatCrabPeerRelay, Nip65Client};
+use crate::notif::NotifHub;
 use crate::order::{FatCrabOrder, FatCrabOrderEnvelope};
+use crate::relay::{
+    spawn_relay_status_watcher, FatCrabRelayReconnectPolicy, RelayMonitor, RelayStore,
+    RELAY_REASON_ADDED, RELAY_REASON_RECONNECT, RELAY_REASON_REMOVED, RELAY_REASON_REQUIREMENTS,
+};
 use crate::taker::{FatCrabBuyTaker, FatCrabSellTaker};
-use crate::types::{BlockchainInfo, Network, ProductionLevel, RelayAddr, RelayInfo};
+use crate::trade::{FatCrabTrade, FatCrabTradeFilter, FatCrabTradeRole, TradeTimestamps};
+use crate::types::{
+    BlockchainInfo, FatCrabNotifOverflowDelegate, FatCrabRelayNotifDelegate,
+    FatCrabTraderNotifDelegate, Network, ProductionLevel, RelayAddr, RelayInfo,
+};
 use crate::RUNTIME;
 
+// State kept by the FFI layer alongside the Trading Engine, shared with every trade handle
+pub(crate) struct TraderContext {
+    // Weak, as the relay watcher and background tasks must not keep the Trading Engine alive
+    pub(crate) inner: Weak<InnerTrader>,
+    pub(crate) trade_timestamps: TradeTimestamps,
+    pub(crate) trade_history: TradeHistory,
+    pub(crate) trade_journal: TradeJournal,
+    pub(crate) notif_hub: NotifHub,
+    pub(crate) event_queue: Arc<EventQueue>,
+    pub(crate) relay_monitor: RelayMonitor,
+    pub(crate) relay_store: RelayStore,
+    pub(crate) nip65_client: Nip65Client,
+    pub(crate) relay_checker: RelayChecker,
+}
+
+impl TraderContext {
+    fn new(inner: Weak<InnerTrader>, app_dir_path: &str) -> Self {
+        let event_queue = Arc::new(EventQueue::new());
+        Self {
+            inner,
+            trade_timestamps: TradeTimestamps::new(app_dir_path),
+            trade_history: TradeHistory::new(app_dir_path),
+            trade_journal: TradeJournal::new(app_dir_path),
+            notif_hub: NotifHub::new(app_dir_path, event_queue.clone()),
+            event_queue,
+            relay_monitor: RelayMonitor::new(),
+            relay_store: RelayStore::new(app_dir_path),
+            nip65_client: Nip65Client::new(),
+            relay_checker: RelayChecker::new(),
+        }
+    }
+}
+
+pub struct FatCrabTraderOptions {
+    pub mnemonic: Option<String>,
+    // Re-adds the relays persisted from previous launches, or starts from a clean list
+    pub restore_relays: bool,
+}
+
+impl Default for FatCrabTraderOptions {
+    fn default() -> Self {
+        Self {
+            mnemonic: None,
+            restore_relays: true,
+        }
+    }
+}
+
 pub struct FatCrabTrader {
-    inner: InnerTrader,
+    inner: Arc<InnerTrader>,
+    ctx: Arc<TraderContext>,
 }
 
 impl FatCrabTrader {
     pub fn new(prod_lvl: ProductionLevel, info: BlockchainInfo, app_dir_path: String) -> Self {
-        let inner =
-            RUNTIME.block_on(async { InnerTrader::new(prod_lvl, info.into(), app_dir_path).await });
-        Self { inner }
+        let options = FatCrabTraderOptions::default();
+        match Self::new_with_options(prod_lvl, info, app_dir_path, options) {
+            Ok(trader) => trader,
+            Err(error) => panic!("Cannot create trader - {}", error),
+        }
     }
 
     pub fn new_with_mnemonic(
@@ -34,18 +101,99 @@ impl FatCrabTrader {
         info: BlockchainInfo,
         app_dir_path: String,
     ) -> Self {
-        let entropy = match bip39::Mnemonic::parse(mnemonic) {
-            Ok(mnemonic) => mnemonic.to_entropy(),
-            Err(error) => panic!("Invalid mnemonic - {}", error),
+        let options = FatCrabTraderOptions {
+            mnemonic: Some(mnemonic),
+            ..Default::default()
         };
-        let secret_key = match SecretKey::from_slice(&entropy) {
-            Ok(secret_key) => secret_key,
-            Err(error) => panic!("Cannot make key from mnemonic - {}", error),
+        match Self::new_with_options(prod_lvl, info, app_dir_path, options) {
+            Ok(trader) => trader,
+            Err(error) => panic!("Cannot create trader - {}", error),
+        }
+    }
+
+    pub fn new_with_options(
+        prod_lvl: ProductionLevel,
+        info: BlockchainInfo,
+        app_dir_path: String,
+        options: FatCrabTraderOptions,
+    ) -> Result<Self, FatCrabError> {
+        let ffi_app_dir_path = app_dir_path.clone();
+        let inner = match options.mnemonic {
+            Some(mnemonic) => {
+                let secret_key = secret_key_from_mnemonic(mnemonic)?;
+                RUNTIME.block_on(async {
+                    InnerTrader::new_with_key(prod_lvl, secret_key, info.into(), app_dir_path).await
+                })
+            }
+            None => RUNTIME
+                .block_on(async { InnerTrader::new(prod_lvl, info.into(), app_dir_path).await }),
         };
-        let inner = RUNTIME.block_on(async {
-            InnerTrader::new_with_key(prod_lvl, secret_key, info.into(), app_dir_path).await
-        });
-        Self { inner }
+        let inner = Arc::new(inner);
+        let ctx = Arc::new(TraderContext::new(
+            Arc::downgrade(&inner),
+            &ffi_app_dir_path,
+        ));
+        spawn_relay_status_watcher(Arc::downgrade(&inner), ctx.clone());
+        let trader = Self { inner, ctx };
+        trader.attach_restored_trades();
+
+        if options.restore_relays {
+            trader.restore_relays();
+        } else if let Err(error) = trader.ctx.relay_store.clear() {
+            warn!("Failed to clear the persisted relay list - {}", error);
+        }
+        Ok(trader)
+    }
+
+    // One at a time, a relay that fails to be added again does not hold back the others
+    fn restore_relays(&self) {
+        for relay_addr in self.ctx.relay_store.relays() {
+            let url = relay_addr.url.clone();
+            if let Err(error) = self.add_relays(vec![relay_addr]) {
+                warn!("Failed to restore relay {} - {}", url, error);
+            }
+        }
+    }
+
+    // Restored trades start capturing notifications right away, before any delegate registers
+    fn attach_restored_trades(&self) {
+        let restored = [
+            (
+                self.get_buy_makers().into_keys().collect::<Vec<_>>(),
+                FatCrabTradeRole::Maker,
+                FatCrabOrderType::Buy,
+            ),
+            (
+                self.get_sell_makers().into_keys().collect(),
+                FatCrabTradeRole::Maker,
+                FatCrabOrderType::Sell,
+            ),
+            (
+                self.get_buy_takers().into_keys().collect(),
+                FatCrabTradeRole::Taker,
+                FatCrabOrderType::Buy,
+            ),
+            (
+                self.get_sell_takers().into_keys().collect(),
+                FatCrabTradeRole::Taker,
+                FatCrabOrderType::Sell,
+            ),
+        ];
+        for (trade_uuids, role, order_type) in restored {
+            for trade_uuid in trade_uuids {
+                match self.ctx.trade_journal.entries(&trade_uuid) {
+                    Ok(entries) => self.ctx.trade_timestamps.backfill(&trade_uuid, &entries),
+                    Err(error) => {
+                        warn!("Failed to read journal of trade {} - {}", trade_uuid, error)
+                    }
+                }
+                self.ctx.notif_hub.restored(FatCrabEvent::TradeRestored {
+                    trade_uuid,
+                    role,
+                    order_type: order_type.clone(),
+                });
+            }
+        }
     }
 
     pub fn wallet_bip39_mnemonic(&self) -> Result<String, FatCrabError> {
@@ -77,10 +225,18 @@ impl FatCrabTrader {
     ) -> Result<String, FatCrabError> {
         let address = Address::from_str(&address).unwrap();
         let address = address.require_network(self.inner.get_network()).unwrap();
+        let address_string = address.to_string();
         let result =
             RUNTIME.block_on(async { self.inner.wallet_send_to_address(address, amount).await });
         match result {
-            Ok(txid) => Ok(txid.to_string()),
+            Ok(txid) => {
+                self.ctx.notif_hub.emit(FatCrabEvent::WalletTxSent {
+                    txid: txid.to_string(),
+                    address: address_string,
+                    amount,
+                });
+                Ok(txid.to_string())
+            }
             Err(e) => Err(e.into()),
         }
     }
@@ -92,9 +248,20 @@ impl FatCrabTrader {
     }
 
     pub fn wallet_blockchain_sync(&self) -> Result<(), FatCrabError> {
-        RUNTIME
-            .block_on(async { self.inner.wallet_blockchain_sync().await })
-            .map_err(|e| e.into())
+        if let Err(e) = RUNTIME.block_on(async { self.inner.wallet_blockchain_sync().await }) {
+            return Err(e.into());
+        }
+
+        let height = RUNTIME.block_on(async { self.inner.wallet_blockchain_height().await });
+        let balances = RUNTIME.block_on(async { self.inner.wallet_balances().await });
+        match (height, balances) {
+            (Ok(height), Ok(balances)) => self
+                .ctx
+                .notif_hub
+                .emit(FatCrabEvent::WalletSynced { height, balances }),
+            _ => warn!("Wallet synced but its height or balances could not be read back"),
+        }
+        Ok(())
     }
 
     pub fn nostr_pubkey(&self) -> String {
@@ -110,18 +277,24 @@ impl FatCrabTrader {
     pub fn add_relays(&self, relay_addrs: Vec<RelayAddr>) -> Result<(), FatCrabError> {
         let mut relays = Vec::new();
 
-        for relay_addr in relay_addrs {
-            let socket = match relay_addr.socket_addr {
-                Some(socket_str) => SocketAddr::from_str(&socket_str).ok(),
+        for relay_addr in relay_addrs.iter() {
+            let socket = match &relay_addr.socket_addr {
+                Some(socket_str) => SocketAddr::from_str(socket_str).ok(),
                 None => None,
             };
             let url = Url::parse(&relay_addr.url)?;
+            RUNTIME.block_on(async { self.ctx.relay_checker.check(&url).await })?;
+            self.ctx
+                .relay_monitor
+                .set_reason(url.as_str(), RELAY_REASON_ADDED);
+            self.ctx.relay_monitor.remember(url.as_str());
             relays.push((url, socket));
         }
 
-        RUNTIME
-            .block_on(async { self.inner.add_relays(relays).await })
-            .map_err(|e| e.into())
+        if let Err(e) = RUNTIME.block_on(async { self.inner.add_relays(relays).await }) {
+            return Err(e.into());
+        }
+        self.ctx.relay_store.upsert(&relay_addrs)
     }
 
     pub fn get_relays(&self) -> Vec<RelayInfo> {
@@ -130,35 +303,151 @@ impl FatCrabTrader {
                 .get_relays()
                 .await
                 .into_iter()
-                .map(|relay| relay.into())
+                .map(|relay| {
+                    let mut relay_info: RelayInfo = relay.into();
+                    relay_info.health = self.ctx.relay_monitor.health(&relay_info.url);
+                    relay_info.capability_issues = self.ctx.relay_checker.issues(&relay_info.url);
+                    relay_info
+                })
                 .collect()
         })
     }
 
     pub fn remove_relay(&self, url: String) -> Result<(), FatCrabError> {
         let url = Url::parse(&url)?;
-        RUNTIME
-            .block_on(async { self.inner.remove_relay(url).await })
-            .map_err(|e| e.into())
+        self.ctx
+            .relay_monitor
+            .set_reason(url.as_str(), RELAY_REASON_REMOVED);
+        self.ctx.relay_monitor.forget(url.as_str());
+        let url_string = url.to_string();
+        if let Err(e) = RUNTIME.block_on(async { self.inner.remove_relay(url).await }) {
+            return Err(e.into());
+        }
+        self.ctx.relay_store.remove(&url_string)
     }
 
     pub fn reconnect(&self) -> Result<(), FatCrabError> {
+        for relay in self.get_relays() {
+            self.ctx
+                .relay_monitor
+                .set_reason(&relay.url, RELAY_REASON_RECONNECT);
+        }
         RUNTIME
             .block_on(async { self.inner.reconnect().await })
             .map_err(|e| e.into())
     }
 
+    pub fn set_relay_reconnect_policy(&self, policy: FatCrabRelayReconnectPolicy) {
+        self.ctx.relay_monitor.set_policy(policy);
+    }
+
+    // Checked by add_relays from then on. Every relay already added, restored or connected for
+    // a trade is checked again. Those falling short are disconnected for this session but stay
+    // in the persisted relay list, their URLs are returned.
+    pub fn set_relay_requirements(
+        &self,
+        requirements: FatCrabRelayRequirements,
+    ) -> Result<Vec<String>, FatCrabError> {
+        self.ctx.relay_checker.set_requirements(requirements);
+
+        let mut disconnected = Vec::new();
+        let mut failures = Vec::new();
+        for relay_url in self.relay_urls() {
+            let url = match Url::parse(&relay_url) {
+                Ok(url) => url,
+                Err(error) => {
+                    failures.push(format!("{} - {}", relay_url, FatCrabError::from(error)));
+                    continue;
+                }
+            };
+            let check = RUNTIME.block_on(async { self.ctx.relay_checker.check(&url).await });
+            let Err(error) = check else {
+                continue;
+            };
+
+            warn!("Disconnecting relay {} - {}", relay_url, error);
+            self.ctx
+                .relay_monitor
+                .set_reason(url.as_str(), RELAY_REASON_REQUIREMENTS);
+            self.ctx.relay_monitor.forget(url.as_str());
+            match RUNTIME.block_on(async { self.inner.remove_relay(url).await }) {
+                Ok(_) => disconnected.push(relay_url),
+                Err(error) => {
+                    let error: FatCrabError = error.into();
+                    failures.push(format!("{} - {}", relay_url, error));
+                }
+            }
+        }
+
+        if !failures.is_empty() {
+            return Err(FatCrabError::Simple {
+                description: format!(
+                    "Disconnected [{}], failed to check or disconnect: {}",
+                    disconnected.join(", "),
+                    failures.join("; ")
+                ),
+            });
+        }
+        Ok(disconnected)
+    }
+
+    // Fetches the relay's NIP-11 document without adding it
+    pub fn probe_relay(&self, url: String) -> Result<FatCrabRelayProbe, FatCrabError> {
+        let url = Url::parse(&url)?;
+        Ok(RUNTIME.block_on(async { self.ctx.relay_checker.probe(&url).await }))
+    }
+
+    fn relay_urls(&self) -> Vec<String> {
+        self.get_relays()
+            .into_iter()
+            .map(|relay| relay.url)
+            .collect()
+    }
+
+    // NIP-65 relay lists of the given pubkeys, as found on our own relays
+    pub fn query_peer_relays(
+        &self,
+        pubkeys: Vec<String>,
+    ) -> Result<Vec<FatCrabPeerRelay>, FatCrabError> {
+        let relay_urls = self.relay_urls();
+        RUNTIME.block_on(async { self.ctx.nip65_client.fetch(&relay_urls, &pubkeys).await })
+    }
+
+    // Trades connect to their peer's relays on their own, this is for peers found otherwise
+    pub fn connect_trade_relays(
+        &self,
+        trade_uuid: String,
+        peer_pubkey: String,
+    ) -> Result<Vec<String>, FatCrabError> {
+        RUNTIME.block_on(async {
+            connect_trade_relays(&self.inner, &self.ctx, &trade_uuid, peer_pubkey).await
+        })
+    }
+
+    pub fn release_trade_relays(&self, trade_uuid: String) -> Result<(), FatCrabError> {
+        RUNTIME.block_on(async { release_trade_relays(&self.inner, &self.ctx, &trade_uuid).await });
+        Ok(())
+    }
+
     pub fn new_buy_maker(
         &self,
         order: FatCrabOrder,
         fatcrab_rx_addr: String,
     ) -> Result<Arc<FatCrabBuyMaker>, FatCrabError> {
+        let trade_uuid = order.trade_uuid.clone();
         match RUNTIME.block_on(async {
             self.inner
                 .new_buy_maker(&order.into(), fatcrab_rx_addr)
                 .await
         }) {
-            Ok(maker_access) => Ok(Arc::new(FatCrabBuyMaker::new(maker_access))),
+            Ok(maker_access) => {
+                self.ctx.trade_timestamps.touch(&trade_uuid, "New");
+                let entry = FatCrabJournalEntry::new("NewBuyMaker", None, Some("New"), None);
+                self.ctx.trade_journal.append(&trade_uuid, entry);
+                let handle = FatCrabBuyMaker::new(maker_access, trade_uuid, self.ctx.clone());
+                handle.attach_notifs();
+                Ok(Arc::new(handle))
+            }
             Err(e) => Err(e.into()),
         }
     }
@@ -167,8 +456,16 @@ impl FatCrabTrader {
         &self,
         order: FatCrabOrder,
     ) -> Result<Arc<FatCrabSellMaker>, FatCrabError> {
+        let trade_uuid = order.trade_uuid.clone();
         match RUNTIME.block_on(async { self.inner.new_sell_maker(&order.into()).await }) {
-            Ok(maker_access) => Ok(Arc::new(FatCrabSellMaker::new(maker_access))),
+            Ok(maker_access) => {
+                self.ctx.trade_timestamps.touch(&trade_uuid, "New");
+                let entry = FatCrabJournalEntry::new("NewSellMaker", None, Some("New"), None);
+                self.ctx.trade_journal.append(&trade_uuid, entry);
+                let handle = FatCrabSellMaker::new(maker_access, trade_uuid, self.ctx.clone());
+                handle.attach_notifs();
+                Ok(Arc::new(handle))
+            }
             Err(e) => Err(e.into()),
         }
     }
@@ -190,9 +487,17 @@ impl FatCrabTrader {
         &self,
         order_envelope: Arc<FatCrabOrderEnvelope>,
     ) -> Result<Arc<FatCrabBuyTaker>, FatCrabError> {
+        let trade_uuid = order_envelope.order().trade_uuid;
         let order_envelope = order_envelope.as_ref().clone();
         match RUNTIME.block_on(async { self.inner.new_buy_taker(&order_envelope.into()).await }) {
-            Ok(taker_access) => Ok(Arc::new(FatCrabBuyTaker::new(taker_access))),
+            Ok(taker_access) => {
+                self.ctx.trade_timestamps.touch(&trade_uuid, "New");
+                let entry = FatCrabJournalEntry::new("NewBuyTaker", None, Some("New"), None);
+                self.ctx.trade_journal.append(&trade_uuid, entry);
+                let handle = FatCrabBuyTaker::new(taker_access, trade_uuid, self.ctx.clone());
+                handle.attach_notifs();
+                Ok(Arc::new(handle))
+            }
             Err(e) => Err(e.into()),
         }
     }
@@ -202,82 +507,217 @@ impl FatCrabTrader {
         order_envelope: Arc<FatCrabOrderEnvelope>,
         fatcrab_rx_addr: String,
     ) -> Result<Arc<FatCrabSellTaker>, FatCrabError> {
+        let trade_uuid = order_envelope.order().trade_uuid;
         let order_envelope = order_envelope.as_ref().clone();
         match RUNTIME.block_on(async {
             self.inner
                 .new_sell_taker(&order_envelope.into(), fatcrab_rx_addr)
                 .await
         }) {
-            Ok(taker_access) => Ok(Arc::new(FatCrabSellTaker::new(taker_access))),
+            Ok(taker_access) => {
+                self.ctx.trade_timestamps.touch(&trade_uuid, "New");
+                let entry = FatCrabJournalEntry::new("NewSellTaker", None, Some("New"), None);
+                self.ctx.trade_journal.append(&trade_uuid, entry);
+                let handle = FatCrabSellTaker::new(taker_access, trade_uuid, self.ctx.clone());
+                handle.attach_notifs();
+                Ok(Arc::new(handle))
+            }
             Err(e) => Err(e.into()),
         }
     }
 
     pub fn get_buy_makers(&self) -> HashMap<String, Arc<FatCrabBuyMaker>> {
-        RUNTIME.block_on(async {
-            self.inner
-                .get_buy_makers()
-                .await
-                .into_iter()
-                .map(|(uuid, maker_access)| {
-                    (
-                        uuid.to_string(),
-                        Arc::new(FatCrabBuyMaker::new(maker_access)),
-                    )
-                })
-                .collect()
-        })
+        // Handles are built outside of the runtime, attaching notifications blocks on it
+        let accesses = RUNTIME.block_on(async { self.inner.get_buy_makers().await });
+        accesses
+            .into_iter()
+            .map(|(uuid, maker_access)| {
+                let trade_uuid = uuid.to_string();
+                let handle =
+                    FatCrabBuyMaker::new(maker_access, trade_uuid.clone(), self.ctx.clone());
+                handle.attach_notifs();
+                (trade_uuid, Arc::new(handle))
+            })
+            .collect()
     }
 
     pub fn get_sell_makers(&self) -> HashMap<String, Arc<FatCrabSellMaker>> {
-        RUNTIME.block_on(async {
-            self.inner
-                .get_sell_makers()
-                .await
-                .into_iter()
-                .map(|(uuid, maker_access)| {
-                    (
-                        uuid.to_string(),
-                        Arc::new(FatCrabSellMaker::new(maker_access)),
-                    )
-                })
-                .collect()
-        })
+        // Handles are built outside of the runtime, attaching notifications blocks on it
+        let accesses = RUNTIME.block_on(async { self.inner.get_sell_makers().await });
+        accesses
+            .into_iter()
+            .map(|(uuid, maker_access)| {
+                let trade_uuid = uuid.to_string();
+                let handle =
+                    FatCrabSellMaker::new(maker_access, trade_uuid.clone(), self.ctx.clone());
+                handle.attach_notifs();
+                (trade_uuid, Arc::new(handle))
+            })
+            .collect()
     }
 
     pub fn get_buy_takers(&self) -> HashMap<String, Arc<FatCrabBuyTaker>> {
-        RUNTIME.block_on(async {
-            self.inner
-                .get_buy_takers()
-                .await
-                .into_iter()
-                .map(|(uuid, taker_access)| {
-                    (
-                        uuid.to_string(),
-                        Arc::new(FatCrabBuyTaker::new(taker_access)),
-                    )
-                })
-                .collect()
-        })
+        // Handles are built outside of the runtime, attaching notifications blocks on it
+        let accesses = RUNTIME.block_on(async { self.inner.get_buy_takers().await });
+        accesses
+            .into_iter()
+            .map(|(uuid, taker_access)| {
+                let trade_uuid = uuid.to_string();
+                let handle =
+                    FatCrabBuyTaker::new(taker_access, trade_uuid.clone(), self.ctx.clone());
+                handle.attach_notifs();
+                (trade_uuid, Arc::new(handle))
+            })
+            .collect()
     }
 
     pub fn get_sell_takers(&self) -> HashMap<String, Arc<FatCrabSellTaker>> {
-        RUNTIME.block_on(async {
-            self.inner
-                .get_sell_takers()
-                .await
-                .into_iter()
-                .map(|(uuid, taker_access)| {
-                    (
-                        uuid.to_string(),
-                        Arc::new(FatCrabSellTaker::new(taker_access)),
-                    )
-                })
-                .collect()
-        })
+        // Handles are built outside of the runtime, attaching notifications blocks on it
+        let accesses = RUNTIME.block_on(async { self.inner.get_sell_takers().await });
+        accesses
+            .into_iter()
+            .map(|(uuid, taker_access)| {
+                let trade_uuid = uuid.to_string();
+                let handle =
+                    FatCrabSellTaker::new(taker_access, trade_uuid.clone(), self.ctx.clone());
+                handle.attach_notifs();
+                (trade_uuid, Arc::new(handle))
+            })
+            .collect()
+    }
+
+    // Read only. Each trade is read straight from the Trading Engine, through handles that
+    // are never attached, notifications are left to get_*_makers/takers and register_*.
+    // A trade the Trading Engine fails to describe is left out, not the whole list.
+    pub fn get_trades(
+        &self,
+        filter: Option<FatCrabTradeFilter>,
+    ) -> Result<Vec<FatCrabTrade>, FatCrabError> {
+        let (buy_makers, sell_makers, buy_takers, sell_takers) = RUNTIME.block_on(async {
+            (
+                self.inner.get_buy_makers().await,
+                self.inner.get_sell_makers().await,
+                self.inner.get_buy_takers().await,
+                self.inner.get_sell_takers().await,
+            )
+        });
+        let mut results = Vec::new();
+        for (uuid, access) in buy_makers {
+            let maker = FatCrabBuyMaker::new(access, uuid.to_string(), self.ctx.clone());
+            results.push((uuid, maker.trade()));
+        }
+        for (uuid, access) in sell_makers {
+            let maker = FatCrabSellMaker::new(access, uuid.to_string(), self.ctx.clone());
+            results.push((uuid, maker.trade()));
+        }
+        for (uuid, access) in buy_takers {
+            let taker = FatCrabBuyTaker::new(access, uuid.to_string(), self.ctx.clone());
+            results.push((uuid, taker.trade()));
+        }
+        for (uuid, access) in sell_takers {
+            let taker = FatCrabSellTaker::new(access, uuid.to_string(), self.ctx.clone());
+            results.push((uuid, taker.trade()));
+        }
+
+        let mut trades = Vec::new();
+        for (trade_uuid, result) in results {
+            match result {
+                Ok(trade) => trades.push(trade),
+                Err(error) => warn!("Leaving trade {} out of the list - {}", trade_uuid, error),
+            }
+        }
+
+        if let Some(filter) = filter {
+            trades.retain(|trade| filter.matches(trade));
+        }
+        trades.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.trade_uuid.cmp(&b.trade_uuid))
+        });
+        Ok(trades)
+    }
+
+    pub fn get_trade_history(&self) -> Vec<FatCrabTradeRecord> {
+        self.ctx.trade_history.records()
+    }
+
+    pub fn export_trade_history(
+        &self,
+        format: FatCrabHistoryFormat,
+    ) -> Result<String, FatCrabError> {
+        self.ctx.trade_history.export(format)
+    }
+
+    pub fn get_trade_journal(
+        &self,
+        trade_uuid: String,
+    ) -> Result<Vec<FatCrabJournalEntry>, FatCrabError> {
+        self.ctx.trade_journal.entries(&trade_uuid)
+    }
+
+    // Pull-based alternative to the notification delegates, for hosts that would rather
+    // drain events from their own run loop. Notifications still need to be acknowledged.
+    pub fn next_events(&self, max: u32, timeout_ms: u64) -> Vec<FatCrabEvent> {
+        self.ctx.notif_hub.enable_events();
+        self.ctx
+            .event_queue
+            .next(max as usize, Duration::from_millis(timeout_ms))
+    }
+
+    pub fn register_notif_delegate(
+        &self,
+        delegate: Arc<dyn FatCrabTraderNotifDelegate>,
+    ) -> Result<(), FatCrabError> {
+        self.ctx.notif_hub.register_trader_delegate(delegate);
+        Ok(())
+    }
+
+    pub fn unregister_notif_delegate(&self) -> Result<(), FatCrabError> {
+        self.ctx.notif_hub.unregister_trader_delegate();
+        Ok(())
+    }
+
+    pub fn register_relay_notif_delegate(
+        &self,
+        delegate: Arc<dyn FatCrabRelayNotifDelegate>,
+    ) -> Result<(), FatCrabError> {
+        for notif in self.ctx.relay_monitor.register(delegate.clone()) {
+            let delegate = delegate.clone();
+            self.ctx
+                .notif_hub
+                .run_on_dispatcher(move || delegate.on_relay_status_notif(notif));
+        }
+        Ok(())
+    }
+
+    pub fn unregister_relay_notif_delegate(&self) -> Result<(), FatCrabError> {
+        self.ctx.relay_monitor.unregister();
+        Ok(())
+    }
+
+    // Told about notifications dropped from the buffer of any trade's delegate
+    pub fn register_notif_overflow_delegate(
+        &self,
+        delegate: Arc<dyn FatCrabNotifOverflowDelegate>,
+    ) -> Result<(), FatCrabError> {
+        self.ctx.notif_hub.register_overflow_delegate(delegate);
+        Ok(())
+    }
+
+    pub fn unregister_notif_overflow_delegate(&self) -> Result<(), FatCrabError> {
+        self.ctx.notif_hub.unregister_overflow_delegate();
+        Ok(())
+    }
+
+    // Same as acknowledging through the trade's handle, for apps that only use the Trader
+    pub fn acknowledge_notifs(&self, trade_uuid: String, seq: u64) -> Result<(), FatCrabError> {
+        self.ctx.notif_hub.acknowledge(&trade_uuid, seq)
     }
 
     pub fn shutdown(&self) -> Result<(), FatCrabError> {
+        self.ctx.notif_hub.shutdown();
+
         // TODO: FFI requires shared reference to self as argument
         // But Trader Shutdown is a self consuming method
         // Not sure how to do this at this point. Ommitting for now
@@ -285,3 +725,17 @@ impl FatCrabTrader {
         Ok(())
     }
 }
+
+fn secret_key_from_mnemonic(mnemonic: String) -> Result<SecretKey, FatCrabError> {
+    let entropy = match bip39::Mnemonic::parse(mnemonic) {
+        Ok(mnemonic) => mnemonic.to_entropy(),
+        Err(error) => {
+            return Err(FatCrabError::Simple {
+                description: format!("Invalid mnemonic - {}", error),
+            })
+        }
+    };
+    SecretKey::from_slice(&entropy).map_err(|error| FatCrabError::Simple {
+        description: format!("Cannot make key from mnemonic - {}", error),
+    })
+}
@@ -8,9 +8,14 @@ use fatcrab_trading::RelayInformationDocument as InnerRelayInformationDocument;
 pub use fatcrab_trading::{
     common::ProductionLevel, maker::FatCrabMakerNotif, taker::FatCrabTakerNotif, RelayStatus,
 };
+use serde::{Deserialize, Serialize};
 
+use crate::event::FatCrabEvent;
+use crate::logging::FatCrabLogRecord;
 use crate::maker::FatCrabMakerNotifOfferStruct;
 use crate::maker::FatCrabMakerNotifPeerStruct;
+use crate::notif::FatCrabNotifOverflowStruct;
+use crate::relay::FatCrabRelayStatusNotifStruct;
 use crate::taker::FatCrabTakerNotifPeerStruct;
 use crate::taker::FatCrabTakerNotifTradeRspStruct;
 pub use crate::{
@@ -25,6 +30,18 @@ pub enum FilterLevel {
     Trace,
 }
 
+impl From<&tracing::Level> for FilterLevel {
+    fn from(level: &tracing::Level) -> Self {
+        match *level {
+            tracing::Level::ERROR => FilterLevel::Error,
+            tracing::Level::WARN => FilterLevel::Warn,
+            tracing::Level::INFO => FilterLevel::Info,
+            tracing::Level::DEBUG => FilterLevel::Debug,
+            tracing::Level::TRACE => FilterLevel::Trace,
+        }
+    }
+}
+
 impl From<FilterLevel> for tracing_subscriber::filter::LevelFilter {
     fn from(level: FilterLevel) -> Self {
         match level {
@@ -53,7 +70,7 @@ impl From<Auth> for InnerAuth {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Network {
     Bitcoin,
     Testnet,
@@ -84,6 +101,9 @@ impl Into<Network> for InnerNetwork {
     }
 }
 
+// Mirrors the Trading Engine's BlockchainInfo. The wallet and its chain client live in the
+// Trading Engine, so a new backend such as Esplora or compact block filters has to be added
+// there before it can be offered here.
 pub enum BlockchainInfo {
     Electrum {
         url: String,
@@ -112,11 +132,17 @@ impl From<BlockchainInfo> for InnerBlockchainInfo {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RelayAddr {
     pub url: String,
     pub socket_addr: Option<String>,
+    // Stored only. The Trading Engine reads from and writes to every relay regardless,
+    // the flags are kept for the app until our own NIP-65 relay list can be published.
+    pub read: bool,
+    pub write: bool,
 }
 
+#[derive(Clone)]
 pub struct RelayInformationDocument {
     /// Name
     pub name: Option<String>,
@@ -166,10 +192,22 @@ impl From<InnerRelayInformationDocument> for RelayInformationDocument {
     }
 }
 
+// Kept by the FFI from what it observes of each relay. There are no latency or event counts,
+// the Trading Engine does not share the relay pool's statistics.
+#[derive(Clone, Default)]
+pub struct RelayHealth {
+    pub last_connected_at: Option<u64>,
+    // Times the relay was seen going from connected to disconnected since it last connected
+    pub consecutive_failures: u32,
+}
+
 pub struct RelayInfo {
     pub url: String,
     pub status: RelayStatus,
     pub document: RelayInformationDocument,
+    pub health: RelayHealth,
+    // From the last NIP-11 probe of the relay, empty if it was never probed
+    pub capability_issues: Vec<String>,
 }
 
 impl From<InnerRelayInfo> for RelayInfo {
@@ -178,6 +216,8 @@ impl From<InnerRelayInfo> for RelayInfo {
             url: relay_info.url.to_string(),
             status: relay_info.status,
             document: relay_info.document.into(),
+            health: RelayHealth::default(),
+            capability_issues: Vec::new(),
         }
     }
 }
@@ -185,9 +225,29 @@ impl From<InnerRelayInfo> for RelayInfo {
 pub trait FatCrabMakerNotifDelegate: Sync + Send {
     fn on_maker_offer_notif(&self, offer_notif: FatCrabMakerNotifOfferStruct);
     fn on_maker_peer_notif(&self, peer_notif: FatCrabMakerNotifPeerStruct);
+    fn on_maker_notif_overflow(&self, overflow_notif: FatCrabNotifOverflowStruct);
 }
 
 pub trait FatCrabTakerNotifDelegate: Sync + Send {
     fn on_taker_trade_rsp_notif(&self, trade_rsp_notif: FatCrabTakerNotifTradeRspStruct);
     fn on_taker_peer_notif(&self, peer_notif: FatCrabTakerNotifPeerStruct);
+    fn on_taker_notif_overflow(&self, overflow_notif: FatCrabNotifOverflowStruct);
+}
+
+// Optional, for apps that want every trade's overflows in one place. The delegate
+// registered for the trade is told of its own overflows regardless.
+pub trait FatCrabNotifOverflowDelegate: Sync + Send {
+    fn on_notif_overflow(&self, overflow_notif: FatCrabNotifOverflowStruct);
+}
+
+pub trait FatCrabTraderNotifDelegate: Sync + Send {
+    fn on_trader_event(&self, event: FatCrabEvent);
+}
+
+pub trait FatCrabLogDelegate: Sync + Send {
+    fn on_log(&self, record: FatCrabLogRecord);
+}
+
+pub trait FatCrabRelayNotifDelegate: Sync + Send {
+    fn on_relay_status_notif(&self, relay_status_notif: FatCrabRelayStatusNotifStruct);
 }